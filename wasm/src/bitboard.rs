@@ -0,0 +1,253 @@
+use crate::Position;
+
+/// A set of squares stored as a 64-bit number; the square in a given row and
+/// column corresponds to the bit number 8 * row + column, so a1 is the lowest
+/// bit and h8 the highest one.
+pub type Bitboard = u64;
+
+/// The squares of the same colour as h1.
+pub const LIGHT_SQUARES: Bitboard = 0x55AA_55AA_55AA_55AA;
+
+/// Returns the index of the square at a given position.
+pub fn square(position: Position) -> usize {
+    let (row, column) = position.as_tuple();
+    8 * row as usize + column as usize
+}
+
+/// Returns the position of a square with a given index.
+pub fn position(square: usize) -> Position {
+    Position::new((square / 8) as u8, (square % 8) as u8).unwrap()
+}
+
+/// Returns a bitboard containing a single square.
+pub fn bit(square: usize) -> Bitboard {
+    1 << square
+}
+
+/// Returns an iterator over the indices of the squares in a bitboard, from
+/// the lowest to the highest one.
+pub fn squares(bitboard: Bitboard) -> Squares {
+    Squares(bitboard)
+}
+
+pub struct Squares(Bitboard);
+
+impl Iterator for Squares {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            return None;
+        }
+        let square = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+}
+
+/// Returns, for every square, the squares reachable from it with a single
+/// shift by one of given numbers of rows and columns.
+const fn shift_table(shifts: &[(i8, i8)]) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut square = 0;
+    while square < 64 {
+        let mut idx = 0;
+        while idx < shifts.len() {
+            let row = (square / 8) as i8 + shifts[idx].0;
+            let column = (square % 8) as i8 + shifts[idx].1;
+            // Negative numbers become large ones, so a single comparison
+            // covers both edges of the board.
+            if (row as u8) < 8 && (column as u8) < 8 {
+                table[square] |= 1 << (8 * row + column);
+            }
+            idx += 1;
+        }
+        square += 1;
+    }
+    table
+}
+
+/// Returns, for every square, the squares lying from it in a given direction
+/// up to the edge of the board.
+const fn ray_table(row_shift: i8, column_shift: i8) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut square = 0;
+    while square < 64 {
+        let mut row = (square / 8) as i8 + row_shift;
+        let mut column = (square % 8) as i8 + column_shift;
+        while (row as u8) < 8 && (column as u8) < 8 {
+            table[square] |= 1 << (8 * row + column);
+            row += row_shift;
+            column += column_shift;
+        }
+        square += 1;
+    }
+    table
+}
+
+/// The squares attacked by a knight standing on a given square.
+pub static KNIGHT_ATTACKS: [Bitboard; 64] = shift_table(&[
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+]);
+
+/// The squares attacked by a king standing on a given square.
+pub static KING_ATTACKS: [Bitboard; 64] = shift_table(&[
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+]);
+
+/// The squares attacked by a pawn standing on a given square, indexed by the
+/// player owning the pawn.
+pub static PAWN_ATTACKS: [[Bitboard; 64]; 2] = {
+    let mut table = [[0; 64]; 2];
+    table[crate::Player::White as usize] = shift_table(&[(1, -1), (1, 1)]);
+    table[crate::Player::Black as usize] = shift_table(&[(-1, -1), (-1, 1)]);
+    table
+};
+
+/// A direction in which a piece can slide, along with the squares lying in
+/// that direction from every square.
+struct Direction {
+    rays: [Bitboard; 64],
+    /// True if the indices of the squares grow along the direction, so the
+    /// nearest square of a ray is its lowest bit.
+    is_ascending: bool,
+}
+
+impl Direction {
+    const fn new(row_shift: i8, column_shift: i8) -> Self {
+        Self {
+            rays: ray_table(row_shift, column_shift),
+            is_ascending: row_shift > 0 || (row_shift == 0 && column_shift > 0),
+        }
+    }
+
+    /// Returns the squares attacked in the direction from a given square:
+    /// the ray is cut off behind the nearest occupied square.
+    fn get_attacks(&self, square: usize, occupancy: Bitboard) -> Bitboard {
+        let ray = self.rays[square];
+        let blockers = ray & occupancy;
+        if blockers == 0 {
+            return ray;
+        }
+        let nearest = if self.is_ascending {
+            blockers.trailing_zeros()
+        } else {
+            63 - blockers.leading_zeros()
+        };
+        ray & !self.rays[nearest as usize]
+    }
+}
+
+static ORTHOGONAL_DIRECTIONS: [Direction; 4] = [
+    Direction::new(1, 0),
+    Direction::new(0, 1),
+    Direction::new(-1, 0),
+    Direction::new(0, -1),
+];
+
+static DIAGONAL_DIRECTIONS: [Direction; 4] = [
+    Direction::new(1, 1),
+    Direction::new(1, -1),
+    Direction::new(-1, 1),
+    Direction::new(-1, -1),
+];
+
+/// Returns the squares attacked by a rook standing on a given square, with
+/// the pieces on a given set of squares blocking its way.
+pub fn rook_attacks(square: usize, occupancy: Bitboard) -> Bitboard {
+    ORTHOGONAL_DIRECTIONS.iter().fold(0, |attacks, direction| {
+        attacks | direction.get_attacks(square, occupancy)
+    })
+}
+
+/// Returns the squares attacked by a bishop standing on a given square, with
+/// the pieces on a given set of squares blocking its way.
+pub fn bishop_attacks(square: usize, occupancy: Bitboard) -> Bitboard {
+    DIAGONAL_DIRECTIONS.iter().fold(0, |attacks, direction| {
+        attacks | direction.get_attacks(square, occupancy)
+    })
+}
+
+/// Returns the squares attacked by a queen standing on a given square, with
+/// the pieces on a given set of squares blocking its way.
+pub fn queen_attacks(square: usize, occupancy: Bitboard) -> Bitboard {
+    rook_attacks(square, occupancy) | bishop_attacks(square, occupancy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn squares_of(names: &[&str]) -> Bitboard {
+        names
+            .iter()
+            .map(|name| bit(square(name.parse().unwrap())))
+            .fold(0, |bitboard, bit| bitboard | bit)
+    }
+
+    #[test]
+    fn test_square_conversion() {
+        let e4: Position = "e4".parse().unwrap();
+        assert_eq!(square(e4), 28);
+        assert_eq!(position(28), e4);
+        assert_eq!(
+            squares(squares_of(&["h8", "a1", "c2"])).collect::<Vec<_>>(),
+            [0, 10, 63]
+        );
+    }
+
+    #[test]
+    fn test_leaper_attacks() {
+        assert_eq!(
+            KNIGHT_ATTACKS[square("a1".parse().unwrap())],
+            squares_of(&["b3", "c2"])
+        );
+        assert_eq!(
+            KNIGHT_ATTACKS[square("e4".parse().unwrap())].count_ones(),
+            8
+        );
+        assert_eq!(
+            KING_ATTACKS[square("h8".parse().unwrap())],
+            squares_of(&["g8", "g7", "h7"])
+        );
+        assert_eq!(
+            PAWN_ATTACKS[crate::Player::White as usize][square("a2".parse().unwrap())],
+            squares_of(&["b3"])
+        );
+        assert_eq!(
+            PAWN_ATTACKS[crate::Player::Black as usize][square("e5".parse().unwrap())],
+            squares_of(&["d4", "f4"])
+        );
+    }
+
+    #[test]
+    fn test_sliding_attacks() {
+        let occupancy = squares_of(&["d6", "b4", "d2", "f4", "g7"]);
+        assert_eq!(
+            rook_attacks(square("d4".parse().unwrap()), occupancy),
+            squares_of(&["d5", "d6", "c4", "b4", "d3", "d2", "e4", "f4"])
+        );
+        assert_eq!(
+            bishop_attacks(square("d4".parse().unwrap()), occupancy),
+            squares_of(&["e5", "f6", "g7", "c5", "b6", "a7", "c3", "b2", "a1", "e3", "f2", "g1"])
+        );
+        assert_eq!(
+            queen_attacks(square("a1".parse().unwrap()), 0).count_ones(),
+            21
+        );
+    }
+}
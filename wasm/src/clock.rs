@@ -0,0 +1,310 @@
+use crate::player::{get_opponent, Player};
+use wasm_bindgen::prelude::*;
+
+/// The way the time of a player is extended with every move.
+#[wasm_bindgen]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeBonus {
+    /// No time is added.
+    None,
+    /// A fixed amount of time is added after every move (Fischer increment).
+    Increment,
+    /// The time used for a move is given back after it, but no more than a
+    /// fixed amount (Bronstein delay). The clock runs from the start of the
+    /// move, so the time can run out before the move is made.
+    Delay,
+}
+
+/// A period of a time control, in which a given number of moves has to be
+/// made; a period without a number of moves lasts until the end of the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Period {
+    moves: Option<u32>,
+    time: u64,
+    bonus: TimeBonus,
+    bonus_time: u64,
+}
+
+/// The rules by which the time of the players is measured, e.g. 40 moves in
+/// 90 minutes followed by 30 minutes for the rest of the game. All the times
+/// are given in milliseconds.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimeControl {
+    periods: Vec<Period>,
+}
+
+#[wasm_bindgen]
+impl TimeControl {
+    /// Creates a time control without any periods; they have to be added with
+    /// add_period.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Creates a time control in which the whole game has to be played within
+    /// a given time.
+    pub fn sudden_death(time: u32) -> Self {
+        Self::single_period(time, TimeBonus::None, 0)
+    }
+    /// Creates a time control with a Fischer increment added after every
+    /// move.
+    pub fn fischer(time: u32, increment: u32) -> Self {
+        Self::single_period(time, TimeBonus::Increment, increment)
+    }
+    /// Creates a time control with a Bronstein delay.
+    pub fn bronstein(time: u32, delay: u32) -> Self {
+        Self::single_period(time, TimeBonus::Delay, delay)
+    }
+    /// Adds a period in which a given number of moves has to be made, or
+    /// which lasts until the end of the game if there is no number of moves.
+    /// If the last period has a number of moves, it's repeated.
+    pub fn add_period(
+        &mut self,
+        moves: Option<u32>,
+        time: u32,
+        bonus: TimeBonus,
+        bonus_time: u32,
+    ) -> Result<(), String> {
+        if self
+            .periods
+            .last()
+            .is_some_and(|period| period.moves.is_none())
+        {
+            return Err("The last period lasts until the end of the game".to_string());
+        }
+        if moves == Some(0) {
+            return Err("A period has to consist of at least one move".to_string());
+        }
+        self.periods.push(Period {
+            moves,
+            time: time.into(),
+            bonus,
+            bonus_time: bonus_time.into(),
+        });
+        Ok(())
+    }
+}
+
+impl TimeControl {
+    fn single_period(time: u32, bonus: TimeBonus, bonus_time: u32) -> Self {
+        let mut time_control = Self::new();
+        time_control
+            .add_period(None, time, bonus, bonus_time)
+            .unwrap();
+        time_control
+    }
+}
+
+fn player_index(player: Player) -> usize {
+    match player {
+        Player::White => 0,
+        Player::Black => 1,
+    }
+}
+
+/// The clocks of both players. The clock is driven by timestamps given in
+/// milliseconds, so it doesn't depend on any source of time by itself.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    time_control: TimeControl,
+    /// The time left for each player when their clock was last stopped.
+    remaining: [u64; 2],
+    /// The index of the current period of each player.
+    periods: [usize; 2],
+    /// The number of moves each player has made in their current period.
+    period_moves: [u32; 2],
+    /// The player whose clock is running and the moment it was started.
+    running: Option<(Player, u64)>,
+}
+
+impl Clock {
+    /// Creates stopped clocks following a given time control, which has to
+    /// consist of at least one period.
+    pub fn new(time_control: TimeControl) -> Result<Self, String> {
+        let first_period = time_control
+            .periods
+            .first()
+            .ok_or_else(|| "The time control has no periods".to_string())?;
+        Ok(Self {
+            remaining: [first_period.time; 2],
+            periods: [0; 2],
+            period_moves: [0; 2],
+            running: None,
+            time_control,
+        })
+    }
+    /// Starts the clock of a given player, stopping the other one.
+    pub fn start(&mut self, player: Player, timestamp: u64) {
+        self.stop(timestamp);
+        self.running = Some((player, timestamp));
+    }
+    /// Stops the running clock, if any.
+    pub fn stop(&mut self, timestamp: u64) {
+        if let Some((player, _)) = self.running {
+            self.remaining[player_index(player)] = self.get_remaining(player, timestamp);
+            self.running = None;
+        }
+    }
+    /// Returns the player whose clock is running.
+    pub fn get_running_player(&self) -> Option<Player> {
+        self.running.map(|(player, _)| player)
+    }
+    /// Returns the time left for a given player at a given moment.
+    pub fn get_remaining(&self, player: Player, timestamp: u64) -> u64 {
+        let remaining = self.remaining[player_index(player)];
+        match self.running {
+            Some((running_player, start)) if running_player == player => {
+                remaining.saturating_sub(timestamp.saturating_sub(start))
+            }
+            _ => remaining,
+        }
+    }
+    /// Returns true if the time of a given player has run out.
+    pub fn is_flagged(&self, player: Player, timestamp: u64) -> bool {
+        self.get_remaining(player, timestamp) == 0
+    }
+    /// Finishes the move of the player whose clock is running: their time is
+    /// extended according to the time control and the clock of the opponent
+    /// is started.
+    pub fn press(&mut self, timestamp: u64) {
+        let Some((player, start)) = self.running else {
+            return;
+        };
+        self.stop(timestamp);
+        let idx = player_index(player);
+        let period = self.time_control.periods[self.periods[idx]];
+        if self.remaining[idx] > 0 {
+            match period.bonus {
+                TimeBonus::Increment => self.remaining[idx] += period.bonus_time,
+                TimeBonus::Delay => {
+                    let elapsed = timestamp.saturating_sub(start);
+                    self.remaining[idx] += elapsed.min(period.bonus_time);
+                }
+                TimeBonus::None => (),
+            }
+        }
+        self.period_moves[idx] += 1;
+        if Some(self.period_moves[idx]) == period.moves {
+            // The last period is repeated if it has a number of moves.
+            self.periods[idx] = (self.periods[idx] + 1).min(self.time_control.periods.len() - 1);
+            self.period_moves[idx] = 0;
+            self.remaining[idx] += self.time_control.periods[self.periods[idx]].time;
+        }
+        self.start(get_opponent(player), timestamp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: u64 = 60_000;
+
+    /// Makes a move of the player whose clock is running, taking a given time.
+    fn make_move(clock: &mut Clock, now: &mut u64, time: u64) {
+        *now += time;
+        clock.press(*now);
+    }
+
+    #[test]
+    fn test_sudden_death() {
+        let mut clock = Clock::new(TimeControl::sudden_death(MINUTE as u32)).unwrap();
+        clock.start(Player::White, 1000);
+        assert_eq!(clock.get_remaining(Player::White, 1000), MINUTE);
+        assert_eq!(clock.get_remaining(Player::White, 21_000), 40_000);
+        assert_eq!(clock.get_remaining(Player::Black, 21_000), MINUTE);
+        let mut now = 1000;
+        make_move(&mut clock, &mut now, 20_000);
+        assert_eq!(clock.get_running_player(), Some(Player::Black));
+        assert_eq!(clock.get_remaining(Player::White, now + 5000), 40_000);
+        assert!(!clock.is_flagged(Player::Black, now + MINUTE - 1));
+        assert!(clock.is_flagged(Player::Black, now + MINUTE));
+        // Timestamps from the past don't add any time.
+        assert_eq!(clock.get_remaining(Player::Black, 0), MINUTE);
+    }
+
+    #[test]
+    fn test_fischer_increment() {
+        let mut clock = Clock::new(TimeControl::fischer(MINUTE as u32, 2000)).unwrap();
+        let mut now = 0;
+        clock.start(Player::White, now);
+        make_move(&mut clock, &mut now, 1000);
+        make_move(&mut clock, &mut now, 5000);
+        assert_eq!(clock.get_remaining(Player::White, now), MINUTE + 1000);
+        assert_eq!(clock.get_remaining(Player::Black, now), MINUTE - 3000);
+    }
+
+    #[test]
+    fn test_bronstein_delay() {
+        let mut clock = Clock::new(TimeControl::bronstein(MINUTE as u32, 3000)).unwrap();
+        let mut now = 0;
+        clock.start(Player::White, now);
+        // The clock runs during the delay; the time is given back only once
+        // the move is made.
+        assert_eq!(clock.get_remaining(Player::White, 2000), MINUTE - 2000);
+        assert_eq!(clock.get_remaining(Player::White, 4000), MINUTE - 4000);
+        make_move(&mut clock, &mut now, 2000);
+        make_move(&mut clock, &mut now, 10_000);
+        assert_eq!(clock.get_remaining(Player::White, now), MINUTE);
+        assert_eq!(clock.get_remaining(Player::Black, now), MINUTE - 7000);
+    }
+
+    #[test]
+    fn test_bronstein_flag_fall_within_delay() {
+        // With 1 second left and a 3 second delay, a 2 second move is too
+        // slow.
+        let mut clock = Clock::new(TimeControl::bronstein(1000, 3000)).unwrap();
+        clock.start(Player::White, 0);
+        assert!(!clock.is_flagged(Player::White, 999));
+        assert!(clock.is_flagged(Player::White, 1000));
+        assert!(clock.is_flagged(Player::White, 2000));
+        // A flagged player gets nothing back.
+        clock.press(2000);
+        assert_eq!(clock.get_remaining(Player::White, 2000), 0);
+    }
+
+    #[test]
+    fn test_multiple_periods() {
+        // 2 moves in 10 minutes, then 5 minutes for the rest of the game,
+        // with a 30 second increment from the first move.
+        let mut time_control = TimeControl::new();
+        time_control
+            .add_period(Some(2), 10 * MINUTE as u32, TimeBonus::Increment, 30_000)
+            .unwrap();
+        time_control
+            .add_period(None, 5 * MINUTE as u32, TimeBonus::Increment, 30_000)
+            .unwrap();
+        assert!(time_control
+            .add_period(None, MINUTE as u32, TimeBonus::None, 0)
+            .is_err());
+        let mut clock = Clock::new(time_control).unwrap();
+        let mut now = 0;
+        clock.start(Player::White, now);
+        for _ in 0..4 {
+            make_move(&mut clock, &mut now, MINUTE);
+        }
+        assert_eq!(clock.get_remaining(Player::White, now), 14 * MINUTE);
+        make_move(&mut clock, &mut now, MINUTE);
+        assert_eq!(
+            clock.get_remaining(Player::White, now),
+            13 * MINUTE + 30_000
+        );
+    }
+
+    #[test]
+    fn test_repeated_period() {
+        let mut time_control = TimeControl::new();
+        time_control
+            .add_period(Some(1), MINUTE as u32, TimeBonus::None, 0)
+            .unwrap();
+        let mut clock = Clock::new(time_control).unwrap();
+        let mut now = 0;
+        clock.start(Player::White, now);
+        make_move(&mut clock, &mut now, 10_000);
+        make_move(&mut clock, &mut now, 0);
+        make_move(&mut clock, &mut now, 10_000);
+        assert_eq!(clock.get_remaining(Player::White, now), 3 * MINUTE - 20_000);
+        assert!(Clock::new(TimeControl::new()).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::gamestate::{GameState, PositionKey};
+use crate::search::{Search, SearchLimit, SearchResult};
+use crate::transposition::TranspositionTable;
+use crate::Move;
+use wasm_bindgen::prelude::*;
+
+/// The strength of the computer player. The weaker levels search shallower,
+/// choose randomly among the moves close to the best one and now and then
+/// make a blunder on purpose.
+#[wasm_bindgen]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Difficulty {
+    Beginner,
+    Easy,
+    Medium,
+    Hard,
+    Maximum,
+}
+
+/// The way a difficulty level weakens the play.
+struct Skill {
+    depth: u32,
+    nodes: u32,
+    /// How many centipawns worse than the best move a move may be to still
+    /// be considered; the closer to the best one, the likelier it's chosen.
+    margin: i32,
+    /// The chance of playing a random move instead, in percent.
+    blunder_chance: u64,
+}
+
+impl Difficulty {
+    fn get_skill(self) -> Skill {
+        let (depth, nodes, margin, blunder_chance) = match self {
+            Difficulty::Beginner => (1, 2_000, 200, 20),
+            Difficulty::Easy => (2, 10_000, 100, 10),
+            Difficulty::Medium => (3, 50_000, 50, 4),
+            Difficulty::Hard => (4, 200_000, 20, 1),
+            Difficulty::Maximum => (5, 500_000, 0, 0),
+        };
+        Skill {
+            depth,
+            nodes,
+            margin,
+            blunder_chance,
+        }
+    }
+}
+
+/// A small pseudorandom generator (SplitMix64), so the choices of the
+/// computer can be reproduced from a seed.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut value = self.0;
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        value ^ (value >> 31)
+    }
+
+    /// Returns a number lower than a given bound.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+}
+
+/// Chooses the move of the computer playing at a given difficulty; the
+/// result has no move if there are no legal moves. The search is limited by
+/// depth and nodes only, and the randomness depends on the seed and the
+/// position, so the same seed always gives the same move in the same
+/// position. For the same reason the given transposition table is cleared
+/// before the search: the scores found by a search limited by nodes depend
+/// on the positions stored by the earlier ones.
+pub fn choose_move(
+    state: &GameState,
+    difficulty: Difficulty,
+    seed: u64,
+    history_keys: Vec<PositionKey>,
+    table: &mut TranspositionTable,
+) -> SearchResult {
+    let skill = difficulty.get_skill();
+    let limit = SearchLimit::depth_and_nodes(skill.depth, skill.nodes);
+    table.clear();
+    let mut search = Search::new(limit, history_keys, table);
+    let scored_moves = search.score_moves(state);
+    let Some(&(_, best_score)) = scored_moves.first() else {
+        return SearchResult {
+            best_move: None,
+            score: 0,
+            depth: 0,
+            nodes: search.get_nodes(),
+        };
+    };
+    let result = |(r#move, score): (Move, i32)| SearchResult {
+        best_move: Some(r#move),
+        score,
+        depth: search.get_depth(),
+        nodes: search.get_nodes(),
+    };
+    let mut random = Random(seed ^ state.get_position_key());
+
+    if random.below(100) < skill.blunder_chance {
+        return result(scored_moves[random.below(scored_moves.len() as u64) as usize]);
+    }
+    // Every candidate gets a weight decreasing linearly with its distance
+    // from the best score.
+    let candidates = scored_moves
+        .iter()
+        .map(|&(r#move, score)| (r#move, score, skill.margin + 1 - (best_score - score)))
+        .take_while(|&(_, _, weight)| weight > 0)
+        .collect::<Vec<_>>();
+    let total_weight = candidates.iter().map(|&(_, _, weight)| weight as u64).sum();
+    let mut target = random.below(total_weight);
+    for &(r#move, score, weight) in &candidates {
+        if target < weight as u64 {
+            return result((r#move, score));
+        }
+        target -= weight as u64;
+    }
+    unreachable!("The weights of the candidates add up to the total")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    const MIDDLEGAME: &str = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+
+    fn choose(fen: &str, difficulty: Difficulty, seed: u64) -> String {
+        let state = GameState::from_fen(fen).unwrap();
+        let mut table = TranspositionTable::new(1).unwrap();
+        let result = choose_move(&state, difficulty, seed, vec![], &mut table);
+        result.best_move.unwrap().to_uci()
+    }
+
+    #[test]
+    fn test_same_seed_gives_same_move() {
+        for difficulty in [Difficulty::Beginner, Difficulty::Easy] {
+            for seed in 0..5 {
+                assert_eq!(
+                    choose(MIDDLEGAME, difficulty, seed),
+                    choose(MIDDLEGAME, difficulty, seed)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_weak_levels_vary_their_moves() {
+        let moves = (0..20)
+            .map(|seed| choose(MIDDLEGAME, Difficulty::Beginner, seed))
+            .collect::<HashSet<_>>();
+        assert!(moves.len() > 3);
+    }
+
+    #[test]
+    fn test_strong_levels_find_mate() {
+        for seed in 0..5 {
+            assert_eq!(
+                choose("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", Difficulty::Hard, seed),
+                "a1a8"
+            );
+        }
+    }
+
+    #[test]
+    fn test_beginner_blunders() {
+        // Taking the queen is by far the best move, but a beginner sometimes
+        // misses it.
+        let moves = (0..50)
+            .map(|seed| {
+                choose(
+                    "4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1",
+                    Difficulty::Beginner,
+                    seed,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert!(moves.iter().any(|uci| uci == "d1d5"));
+        assert!(moves.iter().any(|uci| uci != "d1d5"));
+    }
+
+    #[test]
+    fn test_no_legal_moves() {
+        let state = GameState::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let mut table = TranspositionTable::new(1).unwrap();
+        let result = choose_move(&state, Difficulty::Easy, 0, vec![], &mut table);
+        assert_eq!(result.best_move, None);
+    }
+
+    #[test]
+    fn test_reused_table() {
+        // A table left over from another search gives the same choice as a
+        // new one.
+        let state = GameState::from_fen(MIDDLEGAME).unwrap();
+        let mut table = TranspositionTable::new(1).unwrap();
+        let choose_with = |table: &mut TranspositionTable, seed| {
+            choose_move(&state, Difficulty::Easy, seed, vec![], table).best_move
+        };
+        for seed in 0..5 {
+            choose_with(&mut table, seed + 100);
+            let mut new_table = TranspositionTable::new(1).unwrap();
+            assert_eq!(
+                choose_with(&mut table, seed),
+                choose_with(&mut new_table, seed)
+            );
+        }
+    }
+}
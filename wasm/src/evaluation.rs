@@ -0,0 +1,482 @@
+use crate::bitboard::{self, Bitboard};
+use crate::gamestate::GameState;
+use crate::piece::PieceType;
+use crate::player::{get_opponent, Player};
+use std::ops::{Add, AddAssign, Mul, Neg, Sub};
+
+/// A pair of values of an evaluation term: one for the middlegame and one for
+/// the endgame. The final score is blended from both by the game phase.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Score {
+    pub middlegame: i32,
+    pub endgame: i32,
+}
+
+impl Score {
+    pub const ZERO: Score = Score::new(0, 0);
+
+    pub const fn new(middlegame: i32, endgame: i32) -> Self {
+        Self {
+            middlegame,
+            endgame,
+        }
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+    fn add(self, other: Score) -> Score {
+        Score::new(
+            self.middlegame + other.middlegame,
+            self.endgame + other.endgame,
+        )
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+    fn sub(self, other: Score) -> Score {
+        self + -other
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+    fn neg(self) -> Score {
+        Score::new(-self.middlegame, -self.endgame)
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Score;
+    fn mul(self, factor: i32) -> Score {
+        Score::new(self.middlegame * factor, self.endgame * factor)
+    }
+}
+
+/// The weights of all the evaluation terms, in centipawns. The defaults are
+/// reasonable hand-picked values; they can be replaced for tuning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalWeights {
+    /// The values of the pieces, indexed by their type.
+    pub piece_values: [Score; 6],
+    /// The bonuses for the pieces of each type standing on each square,
+    /// given for White (a1 is the square 0); Black uses the mirrored squares.
+    pub piece_square_tables: [[Score; 64]; 6],
+    /// The penalty for every pawn standing on a file behind another pawn.
+    pub doubled_pawn: Score,
+    /// The penalty for a pawn without pawns of its player on the adjacent
+    /// files.
+    pub isolated_pawn: Score,
+    /// The bonus for a pawn which no enemy pawn can stop, indexed by the
+    /// number of rows it has advanced.
+    pub passed_pawn: [Score; 8],
+    /// The bonus for every pawn shielding the king from the front.
+    pub pawn_shield: Score,
+    /// The penalty for every attack of an enemy piece on the squares around
+    /// the king.
+    pub king_attack: Score,
+    /// The bonus for every square a piece of a given type can move to, not
+    /// counting the ones attacked by enemy pawns.
+    pub mobility: [Score; 6],
+}
+
+/// The contribution of the pieces of each type to the game phase; the phase
+/// goes from `MAX_PHASE` with all of them on the board (the middlegame) to
+/// zero without any of them (the endgame).
+const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
+const MAX_PHASE: i32 = 24;
+
+/// The piece-square tables written as seen from White's side, with the 8th
+/// rank at the top, so the index of a1 is 56.
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+#[rustfmt::skip]
+const PAWN_ENDGAME_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    80, 80, 80, 80, 80, 80, 80, 80,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    30, 30, 30, 30, 30, 30, 30, 30,
+    15, 15, 15, 15, 15, 15, 15, 15,
+     5,  5,  5,  5,  5,  5,  5,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+   -50,-40,-30,-30,-30,-30,-40,-50,
+   -40,-20,  0,  0,  0,  0,-20,-40,
+   -30,  0, 10, 15, 15, 10,  0,-30,
+   -30,  5, 15, 20, 20, 15,  5,-30,
+   -30,  0, 15, 20, 20, 15,  0,-30,
+   -30,  5, 10, 15, 15, 10,  5,-30,
+   -40,-20,  0,  5,  5,  0,-20,-40,
+   -50,-40,-30,-30,-30,-30,-40,-50,
+];
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+   -20,-10,-10,-10,-10,-10,-10,-20,
+   -10,  0,  0,  0,  0,  0,  0,-10,
+   -10,  0,  5, 10, 10,  5,  0,-10,
+   -10,  5,  5, 10, 10,  5,  5,-10,
+   -10,  0, 10, 10, 10, 10,  0,-10,
+   -10, 10, 10, 10, 10, 10, 10,-10,
+   -10,  5,  0,  0,  0,  0,  5,-10,
+   -20,-10,-10,-10,-10,-10,-10,-20,
+];
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+   -20,-10,-10, -5, -5,-10,-10,-20,
+   -10,  0,  0,  0,  0,  0,  0,-10,
+   -10,  0,  5,  5,  5,  5,  0,-10,
+    -5,  0,  5,  5,  5,  5,  0, -5,
+     0,  0,  5,  5,  5,  5,  0, -5,
+   -10,  5,  5,  5,  5,  5,  0,-10,
+   -10,  0,  5,  0,  0,  0,  0,-10,
+   -20,-10,-10, -5, -5,-10,-10,-20,
+];
+#[rustfmt::skip]
+const KING_TABLE: [i32; 64] = [
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -20,-30,-30,-40,-40,-30,-30,-20,
+   -10,-20,-20,-20,-20,-20,-20,-10,
+    20, 20,  0,  0,  0,  0, 20, 20,
+    20, 30, 10,  0,  0, 10, 30, 20,
+];
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: [i32; 64] = [
+   -50,-40,-30,-20,-20,-30,-40,-50,
+   -30,-20,-10,  0,  0,-10,-20,-30,
+   -30,-10, 20, 30, 30, 20,-10,-30,
+   -30,-10, 30, 40, 40, 30,-10,-30,
+   -30,-10, 30, 40, 40, 30,-10,-30,
+   -30,-10, 20, 30, 30, 20,-10,-30,
+   -30,-30,  0,  0,  0,  0,-30,-30,
+   -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+/// Combines the tables of a piece for both phases into one indexed by the
+/// squares.
+fn piece_square_table(middlegame: &[i32; 64], endgame: &[i32; 64]) -> [Score; 64] {
+    std::array::from_fn(|square| Score::new(middlegame[square ^ 56], endgame[square ^ 56]))
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        Self {
+            piece_values: [
+                Score::new(82, 94),
+                Score::new(337, 281),
+                Score::new(365, 297),
+                Score::new(477, 512),
+                Score::new(1025, 936),
+                Score::ZERO,
+            ],
+            piece_square_tables: [
+                piece_square_table(&PAWN_TABLE, &PAWN_ENDGAME_TABLE),
+                piece_square_table(&KNIGHT_TABLE, &KNIGHT_TABLE),
+                piece_square_table(&BISHOP_TABLE, &BISHOP_TABLE),
+                piece_square_table(&ROOK_TABLE, &ROOK_TABLE),
+                piece_square_table(&QUEEN_TABLE, &QUEEN_TABLE),
+                piece_square_table(&KING_TABLE, &KING_ENDGAME_TABLE),
+            ],
+            doubled_pawn: Score::new(-10, -20),
+            isolated_pawn: Score::new(-10, -15),
+            passed_pawn: [
+                Score::ZERO,
+                Score::new(5, 10),
+                Score::new(10, 20),
+                Score::new(15, 35),
+                Score::new(25, 60),
+                Score::new(40, 90),
+                Score::new(60, 130),
+                Score::ZERO,
+            ],
+            pawn_shield: Score::new(10, 0),
+            king_attack: Score::new(-8, 0),
+            mobility: [
+                Score::ZERO,
+                Score::new(4, 4),
+                Score::new(4, 5),
+                Score::new(2, 4),
+                Score::new(1, 2),
+                Score::ZERO,
+            ],
+        }
+    }
+}
+
+/// The squares of the first file; the other files are its shifts.
+const FILE_A: Bitboard = 0x0101_0101_0101_0101;
+
+/// Returns the squares of the files adjacent to a given one.
+fn adjacent_files(column: usize) -> Bitboard {
+    let left = if column > 0 {
+        FILE_A << (column - 1)
+    } else {
+        0
+    };
+    let right = if column < 7 {
+        FILE_A << (column + 1)
+    } else {
+        0
+    };
+    left | right
+}
+
+/// Returns the squares lying in front of a given square from the point of
+/// view of a given player, on all the files.
+fn rows_ahead(player: Player, square: usize) -> Bitboard {
+    let row = square / 8;
+    match player {
+        Player::White if row < 7 => !0 << (8 * (row + 1)),
+        Player::Black => (1 << (8 * row)) - 1,
+        _ => 0,
+    }
+}
+
+/// Returns the two rows in front of a king of a given player standing on a
+/// given square, where its shielding pawns stand.
+fn shield_rows(player: Player, square: usize) -> Bitboard {
+    let row = square / 8;
+    [1, 2]
+        .into_iter()
+        .filter_map(|distance| match player {
+            Player::White => Some(row + distance).filter(|&row| row < 8),
+            Player::Black => row.checked_sub(distance),
+        })
+        .fold(0, |rows, row| rows | 0xFF << (8 * row))
+}
+
+/// Returns the number of rows a pawn of a given player on a given square has
+/// advanced from its player's first row.
+fn relative_row(player: Player, square: usize) -> usize {
+    match player {
+        Player::White => square / 8,
+        Player::Black => 7 - square / 8,
+    }
+}
+
+/// Returns the static evaluation of a position in centipawns from White's
+/// perspective, without searching any moves.
+pub fn evaluate(state: &GameState, weights: &EvalWeights) -> i32 {
+    let score = evaluate_player(state, weights, Player::White)
+        - evaluate_player(state, weights, Player::Black);
+    let phase = PieceType::ALL
+        .into_iter()
+        .map(|piece_type| {
+            let count = (state.get_piece_bitboard(Player::White, piece_type)
+                | state.get_piece_bitboard(Player::Black, piece_type))
+            .count_ones() as i32;
+            PHASE_WEIGHTS[piece_type as usize] * count
+        })
+        .sum::<i32>()
+        .min(MAX_PHASE);
+    (score.middlegame * phase + score.endgame * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+/// Returns the sum of the evaluation terms of a given player.
+fn evaluate_player(state: &GameState, weights: &EvalWeights, player: Player) -> Score {
+    let opponent = get_opponent(player);
+    let own = state.get_occupancy(player);
+    let occupancy = own | state.get_occupancy(opponent);
+    let pawns = state.get_piece_bitboard(player, PieceType::Pawn);
+    let enemy_pawns = state.get_piece_bitboard(opponent, PieceType::Pawn);
+    let enemy_pawn_attacks = bitboard::squares(enemy_pawns).fold(0, |attacks, square| {
+        attacks | bitboard::PAWN_ATTACKS[opponent as usize][square]
+    });
+    let enemy_king_zone = bitboard::squares(state.get_piece_bitboard(opponent, PieceType::King))
+        .fold(0, |zone, square| {
+            zone | bitboard::KING_ATTACKS[square] | bitboard::bit(square)
+        });
+
+    let mut score = Score::ZERO;
+    let mut king_attacks = 0;
+    for piece_type in PieceType::ALL {
+        for square in bitboard::squares(state.get_piece_bitboard(player, piece_type)) {
+            let table_square = match player {
+                Player::White => square,
+                Player::Black => square ^ 56,
+            };
+            score += weights.piece_values[piece_type as usize]
+                + weights.piece_square_tables[piece_type as usize][table_square];
+            let attacks = match piece_type {
+                PieceType::Pawn => bitboard::PAWN_ATTACKS[player as usize][square],
+                PieceType::Knight => bitboard::KNIGHT_ATTACKS[square],
+                PieceType::Bishop => bitboard::bishop_attacks(square, occupancy),
+                PieceType::Rook => bitboard::rook_attacks(square, occupancy),
+                PieceType::Queen => bitboard::queen_attacks(square, occupancy),
+                PieceType::King => bitboard::KING_ATTACKS[square],
+            };
+            let mobility = (attacks & !own & !enemy_pawn_attacks).count_ones() as i32;
+            score += weights.mobility[piece_type as usize] * mobility;
+            king_attacks += (attacks & enemy_king_zone).count_ones() as i32;
+        }
+    }
+    // The penalty of the opponent for the attacks on their king is the bonus
+    // of the player.
+    score = score - weights.king_attack * king_attacks;
+    score += evaluate_pawns(weights, player, pawns, enemy_pawns);
+
+    for square in bitboard::squares(state.get_piece_bitboard(player, PieceType::King)) {
+        let shield =
+            (FILE_A << (square % 8) | adjacent_files(square % 8)) & shield_rows(player, square);
+        score += weights.pawn_shield * (pawns & shield).count_ones() as i32;
+    }
+    score
+}
+
+/// Returns the pawn structure terms of a given player: doubled, isolated and
+/// passed pawns.
+fn evaluate_pawns(
+    weights: &EvalWeights,
+    player: Player,
+    pawns: Bitboard,
+    enemy_pawns: Bitboard,
+) -> Score {
+    let mut score = Score::ZERO;
+    for column in 0..8 {
+        let count = (pawns & FILE_A << column).count_ones() as i32;
+        if count > 1 {
+            score += weights.doubled_pawn * (count - 1);
+        }
+    }
+    for square in bitboard::squares(pawns) {
+        let column = square % 8;
+        if pawns & adjacent_files(column) == 0 {
+            score += weights.isolated_pawn;
+        }
+        let front_span = (FILE_A << column | adjacent_files(column)) & rows_ahead(player, square);
+        if enemy_pawns & front_span == 0 {
+            score += weights.passed_pawn[relative_row(player, square)];
+        }
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the FEN of the same position with the colours swapped and the
+    /// board mirrored vertically.
+    fn mirror_fen(fen: &str) -> String {
+        let swap_case = |text: &str| {
+            text.chars()
+                .map(|c| match c.is_ascii_uppercase() {
+                    true => c.to_ascii_lowercase(),
+                    false => c.to_ascii_uppercase(),
+                })
+                .collect::<String>()
+        };
+        let fields = fen.split(' ').collect::<Vec<_>>();
+        let board = fields[0].split('/').rev().collect::<Vec<_>>().join("/");
+        let player = if fields[1] == "w" { "b" } else { "w" };
+        let en_passant = fields[3]
+            .replace('3', "x")
+            .replace('6', "3")
+            .replace('x', "6");
+        format!(
+            "{} {} {} {} {} {}",
+            swap_case(&board),
+            player,
+            swap_case(fields[2]),
+            en_passant,
+            fields[4],
+            fields[5]
+        )
+    }
+
+    fn eval(fen: &str) -> i32 {
+        evaluate(&GameState::from_fen(fen).unwrap(), &EvalWeights::default())
+    }
+
+    #[test]
+    fn test_start_position_is_balanced() {
+        assert_eq!(
+            eval("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            0
+        );
+    }
+
+    #[test]
+    fn test_evaluation_is_symmetric() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "rnbqkbnr/ppp1p1pp/8/8/3pPp2/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3",
+        ] {
+            assert_eq!(eval(fen), -eval(&mirror_fen(fen)), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_material() {
+        // White is a queen up.
+        assert!(eval("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1") > 900);
+        assert!(eval("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR w KQkq - 0 1") < -900);
+    }
+
+    #[test]
+    fn test_pawn_structure() {
+        let weights = EvalWeights::default();
+        // A passed pawn on the 6th rank in front of a blocked one.
+        let passed = evaluate_pawns(
+            &weights,
+            Player::White,
+            bitboard::bit(40),
+            bitboard::bit(55),
+        );
+        assert_eq!(passed, weights.isolated_pawn + weights.passed_pawn[5]);
+        // Doubled pawns on the e file, both isolated and blocked by a pawn
+        // on the d file.
+        let doubled = evaluate_pawns(
+            &weights,
+            Player::Black,
+            bitboard::bit(52) | bitboard::bit(44),
+            bitboard::bit(27),
+        );
+        assert_eq!(doubled, weights.doubled_pawn + weights.isolated_pawn * 2);
+    }
+
+    #[test]
+    fn test_tapered_phase() {
+        // With all the pieces on the board, the king is safer in the corner.
+        assert!(
+            eval("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1BKR w kq - 0 1")
+                > eval("rnbqkbnr/pppppppp/8/8/4K3/8/PPPPPPPP/RNBQ1B1R w kq - 0 1")
+        );
+        // Without them, it should come to the centre.
+        assert!(eval("8/8/8/8/4K3/8/8/k7 w - - 0 1") > eval("8/8/8/8/8/8/8/k5K1 w - - 0 1"));
+    }
+}
@@ -0,0 +1,73 @@
+use crate::Player;
+use wasm_bindgen::prelude::*;
+
+/// The outcome of a match. Variants that end the game with a win carry the
+/// winning player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    Ongoing,
+    Checkmate(Player),
+    Stalemate,
+    DrawByFiftyMoves,
+    DrawByRepetition,
+    DrawByInsufficientMaterial,
+    DrawByAgreement,
+    Resignation(Player),
+    Timeout(Player),
+    /// The time of a player has run out, but the opponent doesn't have
+    /// enough material to checkmate.
+    DrawByTimeout,
+}
+
+/// A flat version of GameResult that can be passed to JS; the winner (if any)
+/// can be obtained separately.
+#[wasm_bindgen]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameStatus {
+    Ongoing,
+    Checkmate,
+    Stalemate,
+    DrawByFiftyMoves,
+    DrawByRepetition,
+    DrawByInsufficientMaterial,
+    DrawByAgreement,
+    Resignation,
+    Timeout,
+    DrawByTimeout,
+}
+
+impl GameResult {
+    /// Returns the status corresponding to the result.
+    pub fn get_status(&self) -> GameStatus {
+        match self {
+            Self::Ongoing => GameStatus::Ongoing,
+            Self::Checkmate(_) => GameStatus::Checkmate,
+            Self::Stalemate => GameStatus::Stalemate,
+            Self::DrawByFiftyMoves => GameStatus::DrawByFiftyMoves,
+            Self::DrawByRepetition => GameStatus::DrawByRepetition,
+            Self::DrawByInsufficientMaterial => GameStatus::DrawByInsufficientMaterial,
+            Self::DrawByAgreement => GameStatus::DrawByAgreement,
+            Self::Resignation(_) => GameStatus::Resignation,
+            Self::Timeout(_) => GameStatus::Timeout,
+            Self::DrawByTimeout => GameStatus::DrawByTimeout,
+        }
+    }
+    /// Returns the winning player if the game has ended with a win.
+    pub fn get_winner(&self) -> Option<Player> {
+        match *self {
+            Self::Checkmate(player) | Self::Resignation(player) | Self::Timeout(player) => {
+                Some(player)
+            }
+            _ => None,
+        }
+    }
+    /// Returns true if the game has ended in a draw.
+    pub fn is_draw(&self) -> bool {
+        !self.is_ongoing() && self.get_winner().is_none()
+    }
+    /// Returns true if the game is still being played.
+    pub fn is_ongoing(&self) -> bool {
+        *self == Self::Ongoing
+    }
+}
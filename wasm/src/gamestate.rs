@@ -1,16 +1,106 @@
-use crate::{piece::Piece, player::get_opponent, Move, PieceType, Player, Position, PromotionType};
+use crate::{
+    bitboard::{self, Bitboard},
+    gameresult::GameResult,
+    piece::Piece,
+    player::get_opponent,
+    zobrist, Move, PieceType, Player, Position,
+};
+use itertools::Itertools;
 
 type Field = Option<Piece>;
 type Board = Vec<Vec<Field>>;
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct GameState {
-    /// The main board structure that contains all the fields.
-    board: Board,
+    /// The squares occupied by the pieces of every type, indexed by the
+    /// player (see `Player as usize`) and then by the piece type.
+    pieces: [[Bitboard; 6]; 2],
+    /// The squares occupied by the pieces of every player.
+    occupancy: [Bitboard; 2],
+    /// The initial squares of the kings and the rooks that haven't moved yet
+    /// and can therefore be used for castling.
+    castling_squares: Bitboard,
     /// The player whose move it is now.
     current_player: Player,
     /// If there is a pawn that can be taken en passant, it's position is
     /// denoted here. There is always at most a single pawn that can be taken
     /// en passant.
     en_passant_square: Option<Position>,
+    /// The number of halfmoves since the last capture or pawn move, used for
+    /// the fifty-move and seventy-five-move rules.
+    halfmove_clock: u32,
+    /// The number of the full move, starting at 1 and incremented after every
+    /// move of the black player.
+    fullmove_number: u32,
+    /// The Zobrist hash of the position, kept up to date with every change
+    /// of the state.
+    hash: u64,
+}
+
+/// The information needed to take back a move made with
+/// `GameState::make_move`: everything the move has changed that can't be
+/// derived from the move itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UndoRecord {
+    r#move: Move,
+    /// The type of the moved piece before a possible promotion.
+    moved_type: PieceType,
+    /// The piece that has been taken, along with its square, which differs
+    /// from the end of the move for an en passant capture.
+    captured: Option<(Player, PieceType, usize)>,
+    castling_squares: Bitboard,
+    en_passant_square: Option<Position>,
+    halfmove_clock: u32,
+    hash: u64,
+}
+
+/// Describes which castling moves are still available to the players; a
+/// right is kept as long as neither the king nor the given rook has moved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CastlingRights {
+    pub white_kingside: bool,
+    pub white_queenside: bool,
+    pub black_kingside: bool,
+    pub black_queenside: bool,
+}
+
+/// The identity of a position used for detecting repetitions: the Zobrist
+/// hash covering the pieces on the board, the player to move, the castling
+/// rights and the pawn that can be taken en passant. It's compatible with the
+/// Polyglot opening book format.
+pub type PositionKey = u64;
+
+/// The number of halfmoves without a capture or a pawn move after which a
+/// player can claim a draw.
+const FIFTY_MOVE_RULE_HALFMOVES: u32 = 100;
+/// The number of halfmoves without a capture or a pawn move after which the
+/// game is automatically drawn.
+const SEVENTY_FIVE_MOVE_RULE_HALFMOVES: u32 = 150;
+
+/// Returns the row on which the pieces of a given player start the game.
+fn get_home_row(player: Player) -> u8 {
+    match player {
+        Player::White => 0,
+        Player::Black => 7,
+    }
+}
+
+/// Returns the square on which the king of a given player starts the game.
+fn get_king_square(player: Player) -> usize {
+    bitboard::square(Position::new(get_home_row(player), 4).unwrap())
+}
+
+/// Returns the square on which the rook of a given player used for castling
+/// on a given side starts the game.
+fn get_rook_square(player: Player, kingside: bool) -> usize {
+    bitboard::square(Position::new(get_home_row(player), if kingside { 7 } else { 0 }).unwrap())
+}
+
+/// Returns the row on which the pawns of a given player start the game.
+fn get_pawn_row(player: Player) -> u8 {
+    match player {
+        Player::White => 1,
+        Player::Black => 6,
+    }
 }
 
 macro_rules! make_board {
@@ -25,84 +115,197 @@ macro_rules! make_board {
 }
 impl GameState {
     pub fn update_en_passant(state: &GameState, r#move: &Move) -> Option<Position> {
-        let en_passant_square: Option<Position>;
         // Sprawdź, czy ostatni ruch był wykonany przez pionka, który przesunął się o dwa pola do przodu
-        let (start_row, start_col) = r#move.get_current_position().as_tuple();
+        let (start_row, _) = r#move.get_current_position().as_tuple();
         let (end_row, end_col) = r#move.get_end_position().as_tuple();
-        if let Some(Piece::Pawn(data, _)) = state.board[start_row as usize][start_col as usize] {
+        if let Some(Piece::Pawn(data, _)) = state.get_piece(r#move.get_current_position()) {
             if (end_row as i32 - start_row as i32).abs() == 2 {
                 // Sprawdź, czy istnieje pionek przeciwnika, który może wykonać ruch "en passant"
-                for &col in &[end_col - 1, end_col + 1] {
-                    if let Some(Piece::Pawn(other_data, _)) =
-                        state.board[end_row as usize][col as usize]
-                    {
+                let end_pos = Position::new(end_row, end_col).unwrap();
+                for side_pos in [end_pos.shifted(0, -1), end_pos.shifted(0, 1)]
+                    .into_iter()
+                    .flatten()
+                {
+                    if let Some(Piece::Pawn(other_data, _)) = state.get_piece(side_pos) {
                         if data.get_player() != other_data.get_player() {
                             // Ustaw `en_passant_square` na pozycję pionka, który może być zniszczony
-                            en_passant_square = Some(Position::new(end_row, end_col).unwrap());
-                            return en_passant_square;
+                            return Some(end_pos);
                         }
                     }
                 }
             }
         }
         // Jeśli żadne z powyższych warunków nie zostało spełnione, ustaw `en_passant_square` na `None`
-        en_passant_square = None;
-        return en_passant_square;
-    }
-    /// Generates the next state that would be the result of a given move and a
-    /// given promotion. This method does NOT check the legality of a given
-    /// move, but only performs it. If a promotion is specified, the moved
-    /// piece is substituted with an appropriate new one.
-    pub fn transform_state(
-        state: &GameState,
-        r#move: Move,
-        promotion: Option<PromotionType>,
-    ) -> Self {
-        let mut new_board = state.board.clone();
+        None
+    }
+    /// Generates the next state that would be the result of a given move.
+    /// This method does NOT check the legality of a given move, but only
+    /// performs it. If the move promotes a pawn, the pawn is substituted with
+    /// an appropriate new piece.
+    pub fn transform_state(state: &GameState, r#move: Move) -> Self {
+        let mut new_state = state.clone();
+        new_state.make_move(r#move);
+        new_state
+    }
+
+    /// Performs a given move on the state itself, the same way as
+    /// `transform_state` does, and returns the record that allows to take it
+    /// back with `unmake_move`.
+    pub fn make_move(&mut self, r#move: Move) -> UndoRecord {
         let (start_row, start_col) = r#move.get_current_position().as_tuple();
         let (end_row, end_col) = r#move.get_end_position().as_tuple();
+        let start = bitboard::square(r#move.get_current_position());
+        let end = bitboard::square(r#move.get_end_position());
+        let en_passant_square = GameState::update_en_passant(self, &r#move);
+        let castling_rights = self.get_castling_rights();
+        let mut undo = UndoRecord {
+            r#move,
+            moved_type: PieceType::Pawn,
+            captured: None,
+            castling_squares: self.castling_squares,
+            en_passant_square: self.en_passant_square,
+            halfmove_clock: self.halfmove_clock,
+            hash: self.hash,
+        };
 
-        let mut moved_piece = new_board[start_row as usize][start_col as usize].take().unwrap();
-        moved_piece.set_position(&Position::new(end_row, end_col).unwrap());
-
-        new_board[end_row as usize][end_col as usize] = Some(moved_piece);
+        let captured_square = if GameState::can_en_passant(self, &r#move) {
+            bitboard::square(Position::new(start_row, end_col).unwrap())
+        } else {
+            end
+        };
+        undo.captured = self
+            .remove_piece(captured_square)
+            .map(|(player, piece_type)| (player, piece_type, captured_square));
+        let (player, piece_type) = self.remove_piece(start).expect("There is no piece to move");
+        undo.moved_type = piece_type;
+        let new_type = match r#move.get_promotion() {
+            Some(promotion_type) if piece_type == PieceType::Pawn => {
+                promotion_type.get_piece_type()
+            }
+            _ => piece_type,
+        };
+        self.put_piece(player, new_type, end);
 
-        if let Some(Piece::Pawn(_, first_move)) = new_board[end_row as usize][end_col as usize] {
-            if first_move {
-                new_board[end_row as usize][end_col as usize] =
-                    Some(Piece::new_pawn(Position::new(end_row, end_col).unwrap(), state.current_player, false));
+        // Castling is denoted by a king moving two squares sideways; the rook
+        // jumps over the king and ends up on the neighbouring square.
+        if piece_type == PieceType::King && start_col.abs_diff(end_col) == 2 {
+            let (rook_start_col, rook_end_col) = if end_col > start_col { (7, 5) } else { (0, 3) };
+            let rook_start = bitboard::square(Position::new(end_row, rook_start_col).unwrap());
+            if let Some((rook_player, rook_type)) = self.remove_piece(rook_start) {
+                let rook_end = bitboard::square(Position::new(end_row, rook_end_col).unwrap());
+                self.put_piece(rook_player, rook_type, rook_end);
             }
         }
-        
-        if let Some(promotion_type) = promotion {
-            let (row, col) = r#move.get_end_position().as_tuple();
-            let promoted_piece = match promotion_type {
-                PromotionType::Knight => {
-                    Piece::new_knight(Position::new(row, col).unwrap(), state.current_player)
-                }
-                PromotionType::Bishop => {
-                    Piece::new_bishop(Position::new(row, col).unwrap(), state.current_player)
-                }
-                PromotionType::Rook => {
-                    Piece::new_rook(Position::new(row, col).unwrap(), state.current_player, true)
-                }
-                PromotionType::Queen => {
-                    Piece::new_queen(Position::new(row, col).unwrap(), state.current_player)
-                }
-            };
-            new_board[row as usize][col as usize] = Some(promoted_piece);
+
+        // A move from or onto the initial square of a king or a rook means
+        // that the piece has moved or has been taken.
+        self.castling_squares &= !(bitboard::bit(start) | bitboard::bit(end));
+
+        self.hash ^= zobrist::castling_key(castling_rights)
+            ^ zobrist::castling_key(self.get_castling_rights())
+            ^ zobrist::en_passant_key(self.en_passant_square)
+            ^ zobrist::en_passant_key(en_passant_square)
+            ^ zobrist::player_key(Player::White);
+        self.en_passant_square = en_passant_square;
+        if undo.captured.is_some() || piece_type == PieceType::Pawn {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
         }
-        Self {
-            board: new_board,
-            current_player: get_opponent(state.current_player),
-            en_passant_square: GameState::update_en_passant(&state, &r#move),
+        if self.current_player == Player::Black {
+            self.fullmove_number += 1;
         }
+        self.current_player = get_opponent(self.current_player);
+        undo
+    }
+
+    /// Takes back the last move made with `make_move`, restoring the state
+    /// from before it.
+    pub fn unmake_move(&mut self, undo: UndoRecord) {
+        self.current_player = get_opponent(self.current_player);
+        if self.current_player == Player::Black {
+            self.fullmove_number -= 1;
+        }
+        let (start_col, end_col) = (
+            undo.r#move.get_current_position().get_column(),
+            undo.r#move.get_end_position().get_column(),
+        );
+        let end_row = undo.r#move.get_end_position().get_row();
+        let start = bitboard::square(undo.r#move.get_current_position());
+        let end = bitboard::square(undo.r#move.get_end_position());
+
+        if undo.moved_type == PieceType::King && start_col.abs_diff(end_col) == 2 {
+            let (rook_start_col, rook_end_col) = if end_col > start_col { (7, 5) } else { (0, 3) };
+            let rook_end = bitboard::square(Position::new(end_row, rook_end_col).unwrap());
+            if let Some((rook_player, rook_type)) = self.remove_piece(rook_end) {
+                let rook_start = bitboard::square(Position::new(end_row, rook_start_col).unwrap());
+                self.put_piece(rook_player, rook_type, rook_start);
+            }
+        }
+        let (player, _) = self
+            .remove_piece(end)
+            .expect("There is no piece to move back");
+        self.put_piece(player, undo.moved_type, start);
+        if let Some((captured_player, captured_type, square)) = undo.captured {
+            self.put_piece(captured_player, captured_type, square);
+        }
+
+        self.castling_squares = undo.castling_squares;
+        self.en_passant_square = undo.en_passant_square;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.hash = undo.hash;
+    }
+
+    /// Returns the player and the type of the piece standing on a given
+    /// square.
+    fn get_piece_kind(&self, square: usize) -> Option<(Player, PieceType)> {
+        let bit = bitboard::bit(square);
+        let player = [Player::White, Player::Black]
+            .into_iter()
+            .find(|&player| self.occupancy[player as usize] & bit != 0)?;
+        PieceType::ALL
+            .into_iter()
+            .find(|&piece_type| self.pieces[player as usize][piece_type as usize] & bit != 0)
+            .map(|piece_type| (player, piece_type))
+    }
+
+    /// Places a piece on an empty square.
+    fn put_piece(&mut self, player: Player, piece_type: PieceType, square: usize) {
+        let bit = bitboard::bit(square);
+        self.pieces[player as usize][piece_type as usize] |= bit;
+        self.occupancy[player as usize] |= bit;
+        self.hash ^= zobrist::piece_key(player, piece_type, square);
+    }
+
+    /// Removes the piece standing on a given square, if any, and returns its
+    /// player and type.
+    fn remove_piece(&mut self, square: usize) -> Option<(Player, PieceType)> {
+        let (player, piece_type) = self.get_piece_kind(square)?;
+        let bit = bitboard::bit(square);
+        self.pieces[player as usize][piece_type as usize] &= !bit;
+        self.occupancy[player as usize] &= !bit;
+        self.hash ^= zobrist::piece_key(player, piece_type, square);
+        Some((player, piece_type))
+    }
+
+    /// Computes the Zobrist hash of the state from scratch.
+    fn compute_hash(&self) -> u64 {
+        let pieces_key = bitboard::squares(self.occupancy[0] | self.occupancy[1])
+            .map(|square| {
+                let (player, piece_type) = self.get_piece_kind(square).unwrap();
+                zobrist::piece_key(player, piece_type, square)
+            })
+            .fold(0, |key, piece_key| key ^ piece_key);
+        pieces_key
+            ^ zobrist::castling_key(self.get_castling_rights())
+            ^ zobrist::en_passant_key(self.en_passant_square)
+            ^ zobrist::player_key(self.current_player)
     }
 
     /// Creates an initial chessboard state.
     pub fn init() -> Self {
-        Self {
-            board: make_board!(
+        Self::from_board(
+            make_board!(
                 Piece::new_rook(Position::new(0, 0).unwrap(), Player::White, true,),
                 Piece::new_knight(Position::new(0, 1).unwrap(), Player::White,),
                 Piece::new_bishop(Position::new(0, 2).unwrap(), Player::White,),
@@ -136,147 +339,724 @@ impl GameState {
                 Piece::new_knight(Position::new(7, 6).unwrap(), Player::Black,),
                 Piece::new_rook(Position::new(7, 7).unwrap(), Player::Black, true,),
             ),
-            current_player: Player::White,
-            en_passant_square: None,
-        }
+            Player::White,
+            None,
+        )
+        .unwrap()
     }
-    /// Creates a state from a given board and other data.
+    /// Creates a state from a given board and other data. The castling flags
+    /// of the kings and the rooks are kept, while the first move flags of the
+    /// pawns are ignored: a pawn can make a double step as long as it stands
+    /// on its initial row.
     pub fn from_board(
         init_board: Board,
         player: Player,
         en_passant_square: Option<Position>,
     ) -> Option<Self> {
-        if init_board.len() == 8 && init_board.iter().all(|vec| vec.len() == 8) {
-            Some(Self {
-                board: init_board,
-                current_player: player,
-                en_passant_square,
-            })
-        } else {
+        if init_board.len() != 8 || init_board.iter().any(|vec| vec.len() != 8) {
+            return None;
+        }
+        let mut state = Self {
+            pieces: [[0; 6]; 2],
+            occupancy: [0; 2],
+            castling_squares: 0,
+            current_player: player,
+            en_passant_square,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            hash: 0,
+        };
+        for (row, fields) in init_board.iter().enumerate() {
+            for (column, field) in fields.iter().enumerate() {
+                if let Some(piece) = field {
+                    state.put_piece(piece.get_player(), piece.get_type(), 8 * row + column);
+                }
+            }
+        }
+        // Only the kings and the rooks standing on their initial squares can
+        // be used for castling.
+        for player in [Player::White, Player::Black] {
+            for (square, piece_type) in [
+                (get_king_square(player), PieceType::King),
+                (get_rook_square(player, true), PieceType::Rook),
+                (get_rook_square(player, false), PieceType::Rook),
+            ] {
+                let position = bitboard::position(square);
+                let field = init_board[position.get_row() as usize][position.get_column() as usize];
+                if field.is_some_and(|piece| {
+                    piece.get_player() == player
+                        && piece.get_type() == piece_type
+                        && piece.can_castle()
+                }) {
+                    state.castling_squares |= bitboard::bit(square);
+                }
+            }
+        }
+        state.hash = state.compute_hash();
+        Some(state)
+    }
+    /// Creates a state from a position written in the Forsyth-Edwards
+    /// Notation. The castling rights are mapped onto the castling flags of the
+    /// kings and the rooks, while the first move flag of every pawn is set if
+    /// it stands on its initial row.
+    pub fn from_fen(fen: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let [placement, player, castling, en_passant, halfmove, fullmove] = fields[..] else {
+            return Err(format!(
+                "FEN must consist of 6 fields, found {}",
+                fields.len()
+            ));
+        };
+
+        let mut board: Board = vec![vec![None; 8]; 8];
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(format!(
+                "Piece placement must describe 8 ranks, found {}",
+                ranks.len()
+            ));
+        }
+        for (rank, row) in ranks.iter().zip((0..8).rev()) {
+            let mut column: u8 = 0;
+            for symbol in rank.chars() {
+                if let Some(empty) = symbol.to_digit(10).filter(|n| (1..=8).contains(n)) {
+                    column = column.saturating_add(empty as u8);
+                } else {
+                    let piece_type = PieceType::from_char(symbol).ok_or_else(|| {
+                        format!("Invalid piece symbol '{}' in rank {}", symbol, row + 1)
+                    })?;
+                    let position = Position::new(row, column)
+                        .ok_or_else(|| format!("Rank {} describes more than 8 squares", row + 1))?;
+                    let owner = if symbol.is_ascii_uppercase() {
+                        Player::White
+                    } else {
+                        Player::Black
+                    };
+                    let first_move = match owner {
+                        Player::White => row == 1,
+                        Player::Black => row == 6,
+                    };
+                    // Kings and rooks get their castling flags from the
+                    // castling availability field later on.
+                    board[row as usize][column as usize] = Some(Piece::new_of_type(
+                        piece_type,
+                        position,
+                        owner,
+                        piece_type == PieceType::Pawn && first_move,
+                    ));
+                    column += 1;
+                }
+            }
+            if column != 8 {
+                return Err(format!(
+                    "Rank {} describes {} squares instead of 8",
+                    row + 1,
+                    column
+                ));
+            }
+        }
+
+        let current_player = match player {
+            "w" => Player::White,
+            "b" => Player::Black,
+            _ => return Err(format!("Invalid active color '{}'", player)),
+        };
+
+        if castling != "-" {
+            if castling.is_empty() || !castling.chars().all(|symbol| "KQkq".contains(symbol)) {
+                return Err(format!("Invalid castling availability '{}'", castling));
+            }
+            for symbol in castling.chars() {
+                let (owner, row) = if symbol.is_ascii_uppercase() {
+                    (Player::White, 0)
+                } else {
+                    (Player::Black, 7)
+                };
+                let rook_column = if symbol.eq_ignore_ascii_case(&'K') {
+                    7
+                } else {
+                    0
+                };
+                for (column, piece_type) in [(4, PieceType::King), (rook_column, PieceType::Rook)] {
+                    let field = &mut board[row as usize][column as usize];
+                    match field {
+                        Some(piece)
+                            if piece.get_player() == owner && piece.get_type() == piece_type =>
+                        {
+                            *field = Some(Piece::new_of_type(
+                                piece_type,
+                                piece.get_position(),
+                                owner,
+                                true,
+                            ));
+                        }
+                        _ => {
+                            return Err(format!(
+                                "Castling right '{}' requires an unmoved king and rook",
+                                symbol
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+
+        let en_passant_square = if en_passant == "-" {
             None
+        } else {
+            let target: Position = en_passant.parse()?;
+            let (expected_row, row_shift) = match current_player {
+                Player::White => (5, -1),
+                Player::Black => (2, 1),
+            };
+            let pawn_position = target
+                .shifted(row_shift, 0)
+                .filter(|_| target.get_row() == expected_row);
+            match pawn_position
+                .and_then(|pos| board[pos.get_row() as usize][pos.get_column() as usize])
+            {
+                Some(Piece::Pawn(data, _)) if data.get_player() != current_player => pawn_position,
+                _ => return Err(format!("Invalid en passant target square '{}'", en_passant)),
+            }
+        };
+
+        let halfmove_clock = halfmove
+            .parse()
+            .map_err(|_| format!("Invalid halfmove clock '{}'", halfmove))?;
+        let fullmove_number = fullmove
+            .parse()
+            .ok()
+            .filter(|&number: &u32| number >= 1)
+            .ok_or_else(|| format!("Invalid fullmove number '{}'", fullmove))?;
+
+        let mut state = Self::from_board(board, current_player, en_passant_square).unwrap();
+        state.halfmove_clock = halfmove_clock;
+        state.fullmove_number = fullmove_number;
+        // Only keep the en passant square if the pawn can actually be taken,
+        // the same way it's done after a double step.
+        state.en_passant_square = state.en_passant_square.filter(|&pos| {
+            [pos.shifted(0, -1), pos.shifted(0, 1)]
+                .into_iter()
+                .flatten()
+                .any(|side_pos| {
+                    matches!(state.get_piece(side_pos), Some(Piece::Pawn(data, _)) if data.get_player() == current_player)
+                })
+        });
+        state.hash = state.compute_hash();
+        for player in [Player::White, Player::Black] {
+            if state.count_kings(player) != 1 {
+                return Err(format!("There must be exactly one {:?} king", player));
+            }
+        }
+        if state.is_player_checked(get_opponent(current_player)) {
+            return Err("The player who is not to move can't be in check".to_string());
         }
+        Ok(state)
+    }
+
+    /// Returns the number of a given player's kings on the board.
+    fn count_kings(&self, player: Player) -> usize {
+        self.pieces[player as usize][PieceType::King as usize].count_ones() as usize
     }
 
-    /// Returns all the moves that can be made by a piece on a given position
+    /// Returns the state written in the Forsyth-Edwards Notation.
+    pub fn to_fen(&self) -> String {
+        let placement = (0..8)
+            .rev()
+            .map(|row| {
+                let mut rank = String::new();
+                let mut empty = 0;
+                for column in 0..8 {
+                    match self.get_piece(Position::new(row, column).unwrap()) {
+                        Some(piece) => {
+                            if empty > 0 {
+                                rank.push_str(&empty.to_string());
+                                empty = 0;
+                            }
+                            rank.push(piece.to_fen_char());
+                        }
+                        None => empty += 1,
+                    }
+                }
+                if empty > 0 {
+                    rank.push_str(&empty.to_string());
+                }
+                rank
+            })
+            .join("/");
+        let player = match self.current_player {
+            Player::White => "w",
+            Player::Black => "b",
+        };
+        let rights = self.get_castling_rights();
+        let castling: String = [
+            (rights.white_kingside, 'K'),
+            (rights.white_queenside, 'Q'),
+            (rights.black_kingside, 'k'),
+            (rights.black_queenside, 'q'),
+        ]
+        .into_iter()
+        .filter_map(|(right, symbol)| right.then_some(symbol))
+        .collect();
+        let en_passant = self
+            .en_passant_square
+            .and_then(
+                |pos| match self.get_piece(pos).map(|piece| piece.get_player()) {
+                    Some(Player::White) => pos.shifted(-1, 0),
+                    Some(Player::Black) => pos.shifted(1, 0),
+                    None => None,
+                },
+            )
+            .map(|pos| pos.to_string());
+        format!(
+            "{} {} {} {} {} {}",
+            placement,
+            player,
+            if castling.is_empty() { "-" } else { &castling },
+            en_passant.as_deref().unwrap_or("-"),
+            self.halfmove_clock,
+            self.fullmove_number
+        )
+    }
+
+    /// Returns all the moves that can be made by a piece on a given position,
+    /// regardless of whether they leave the player's own king in check
     /// (returns an empty vector if there is no piece there).
-    pub fn get_moves(&self, position: Position) -> Vec<Move> {
-        let (row, col) = position.as_tuple();
-        println!("row: {}, col: {}", row, col);
-        match &self.board[row as usize][col as usize] {
-            Some(piece) => piece.get_moves(&self),
-            None => vec![],
+    pub fn get_pseudo_legal_moves(&self, position: Position) -> Vec<Move> {
+        let square = bitboard::square(position);
+        let Some((player, piece_type)) = self.get_piece_kind(square) else {
+            return vec![];
+        };
+        let own = self.occupancy[player as usize];
+        let enemy = self.occupancy[get_opponent(player) as usize];
+        let occupancy = own | enemy;
+        let targets = match piece_type {
+            PieceType::Pawn => return self.get_pawn_moves(position, player),
+            PieceType::Knight => bitboard::KNIGHT_ATTACKS[square],
+            PieceType::Bishop => bitboard::bishop_attacks(square, occupancy),
+            PieceType::Rook => bitboard::rook_attacks(square, occupancy),
+            PieceType::Queen => bitboard::queen_attacks(square, occupancy),
+            PieceType::King => bitboard::KING_ATTACKS[square],
+        };
+        let mut result = bitboard::squares(targets & !own)
+            .map(|target| {
+                let flags = if enemy & bitboard::bit(target) != 0 {
+                    Move::CAPTURE
+                } else {
+                    0
+                };
+                Move::with_flags(position, bitboard::position(target), flags)
+            })
+            .collect_vec();
+        if piece_type == PieceType::King {
+            result.extend(
+                [false, true]
+                    .into_iter()
+                    .filter_map(|kingside| self.get_castle_move(player, kingside)),
+            );
         }
+        result
     }
 
-    /// Returns all the legal moves that can be made by the current player.
-    fn get_all_moves(&self) -> Vec<Move> {
+    /// Returns the moves of a pawn of a given player standing on a given
+    /// position; a pawn reaching the last rank has to be promoted, so every
+    /// such move comes in a variant for each promotion piece.
+    fn get_pawn_moves(&self, position: Position, player: Player) -> Vec<Move> {
+        let square = bitboard::square(position);
+        let enemy = self.occupancy[get_opponent(player) as usize];
+        let is_empty = |pos: Position| self.get_piece_kind(bitboard::square(pos)).is_none();
+        let row_shift: i8 = if player == Player::White { 1 } else { -1 };
         let mut result = vec![];
-        for field in self.board.iter().flatten() {
-            if let Some(piece) = field {
-                if piece.get_player() == self.current_player {
-                    result.append(&mut piece.get_moves(&self));
+        let mut push_move = |r#move: Move| {
+            if matches!(r#move.get_end_position().get_row(), 0 | 7) {
+                result.extend(
+                    crate::PromotionType::ALL
+                        .map(|promotion| r#move.with_promotion(Some(promotion))),
+                );
+            } else {
+                result.push(r#move);
+            }
+        };
+        if let Some(to_pos) = position.shifted(row_shift, 0).filter(|&pos| is_empty(pos)) {
+            push_move(Move::new(position, to_pos));
+            if position.get_row() == get_pawn_row(player) {
+                if let Some(to_pos) = position
+                    .shifted(2 * row_shift, 0)
+                    .filter(|&pos| is_empty(pos))
+                {
+                    push_move(Move::with_flags(position, to_pos, Move::DOUBLE_PUSH));
+                }
+            }
+        }
+        for target in bitboard::squares(bitboard::PAWN_ATTACKS[player as usize][square] & enemy) {
+            push_move(Move::with_flags(
+                position,
+                bitboard::position(target),
+                Move::CAPTURE,
+            ));
+        }
+        // The pawn that has just made a double step can be taken by a pawn
+        // standing beside it, which moves onto the square it has skipped.
+        if let Some(side_pos) = self.en_passant_square {
+            let is_capturable = matches!(
+                self.get_piece(side_pos),
+                Some(piece) if piece.get_player() != player && piece.get_type() == PieceType::Pawn
+            );
+            if is_capturable
+                && side_pos.get_row() == position.get_row()
+                && side_pos.get_column().abs_diff(position.get_column()) == 1
+            {
+                if let Some(to_pos) = side_pos.shifted(row_shift, 0) {
+                    push_move(Move::with_flags(
+                        position,
+                        to_pos,
+                        Move::CAPTURE | Move::EN_PASSANT,
+                    ));
                 }
             }
         }
         result
     }
 
-    /// Returns all the legal moves that can be made by the other player.
-    fn get_all_opponent_moves(&self) -> Vec<Move> {
-        let mut result = vec![];
-        for field in self.board.iter().flatten() {
-            if let Some(piece) = field {
-                if piece.get_player() != self.current_player {
-                    result.append(&mut piece.get_moves(&self));
-                }
+    /// Returns the castling move of a given player on a given side of the
+    /// board if it's possible: the squares between the king and the rook have
+    /// to be empty, and the king can't castle out of, through or into a
+    /// check.
+    fn get_castle_move(&self, player: Player, kingside: bool) -> Option<Move> {
+        if !self.can_castle_on_side(player, kingside) {
+            return None;
+        }
+        let row = get_home_row(player);
+        let (empty_columns, safe_columns) = if kingside {
+            (5..=6, 4..=6)
+        } else {
+            (1..=3, 2..=4)
+        };
+        let opponent = get_opponent(player);
+        let square_at = |column| Position::new(row, column).unwrap();
+        if empty_columns
+            .into_iter()
+            .all(|column| self.get_piece(square_at(column)).is_none())
+            && safe_columns
+                .into_iter()
+                .all(|column| !self.is_attacked(square_at(column), opponent))
+        {
+            Some(Move::with_flags(
+                square_at(4),
+                square_at(if kingside { 6 } else { 2 }),
+                Move::CASTLE,
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Returns all the legal moves that can be made by a piece on a given
+    /// position (returns an empty vector if there is no piece there or if the
+    /// piece doesn't belong to the current player).
+    pub fn get_moves(&self, position: Position) -> Vec<Move> {
+        match self.get_piece(position) {
+            Some(piece) if piece.get_player() == self.current_player => self
+                .get_pseudo_legal_moves(position)
+                .into_iter()
+                .filter(|r#move| self.is_legal(*r#move))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Returns true if a given pseudo-legal move doesn't leave the mover's
+    /// king in check.
+    fn is_legal(&self, r#move: Move) -> bool {
+        let new_state = GameState::transform_state(self, r#move);
+        !new_state.is_player_checked(self.current_player)
+    }
+
+    /// Returns all the moves that can be made by the current player,
+    /// regardless of whether they leave their king in check.
+    pub fn get_all_pseudo_legal_moves(&self) -> Vec<Move> {
+        bitboard::squares(self.occupancy[self.current_player as usize])
+            .flat_map(|square| self.get_pseudo_legal_moves(bitboard::position(square)))
+            .collect()
+    }
+
+    /// Returns all the legal moves that can be made by the current player.
+    pub fn get_all_moves(&self) -> Vec<Move> {
+        bitboard::squares(self.occupancy[self.current_player as usize])
+            .flat_map(|square| self.get_moves(bitboard::position(square)))
+            .collect()
+    }
+
+    /// Returns the number of the leaf nodes of the tree of legal moves of a
+    /// given depth growing from the state, used for validating the move
+    /// generator against known counts.
+    pub fn perft(&self, depth: u32) -> u64 {
+        self.clone().count_leaves(depth)
+    }
+
+    /// Counts the perft leaf nodes by making and taking back the moves on the
+    /// state itself.
+    fn count_leaves(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.get_all_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves
+            .into_iter()
+            .map(|r#move| {
+                let undo = self.make_move(r#move);
+                let nodes = self.count_leaves(depth - 1);
+                self.unmake_move(undo);
+                nodes
+            })
+            .sum()
+    }
+
+    /// Returns the perft count of a given depth split between the legal moves
+    /// of the state, which helps to find the move that is generated wrongly.
+    pub fn perft_divide(&self, depth: u32) -> Vec<(Move, u64)> {
+        self.get_all_moves()
+            .into_iter()
+            .map(|r#move| {
+                let nodes = GameState::transform_state(self, r#move).perft(depth.saturating_sub(1));
+                (r#move, nodes)
+            })
+            .collect()
+    }
+
+    /// Returns the result of the game as it can be determined from the state
+    /// alone: a checkmate, a stalemate, an automatic draw by the
+    /// seventy-five-move rule or an ongoing game.
+    pub fn get_result(&self) -> GameResult {
+        if self.get_all_moves().is_empty() {
+            if self.is_checked() {
+                GameResult::Checkmate(get_opponent(self.current_player))
+            } else {
+                GameResult::Stalemate
+            }
+        } else if self.has_insufficient_material() {
+            GameResult::DrawByInsufficientMaterial
+        } else if self.halfmove_clock >= SEVENTY_FIVE_MOVE_RULE_HALFMOVES {
+            GameResult::DrawByFiftyMoves
+        } else {
+            GameResult::Ongoing
+        }
+    }
+
+    /// Returns the squares occupied by the pieces of a given type of both
+    /// players.
+    fn get_both_players(&self, piece_type: PieceType) -> Bitboard {
+        self.pieces[0][piece_type as usize] | self.pieces[1][piece_type as usize]
+    }
+
+    /// Returns true if neither player can possibly checkmate the other, i.e.
+    /// there are only kings left on the board, a single minor piece beside
+    /// them, or only bishops that all stand on squares of the same colour.
+    pub fn has_insufficient_material(&self) -> bool {
+        let pieces =
+            (self.occupancy[0] | self.occupancy[1]) & !self.get_both_players(PieceType::King);
+        let minor_pieces =
+            self.get_both_players(PieceType::Bishop) | self.get_both_players(PieceType::Knight);
+        let bishops = self.get_both_players(PieceType::Bishop);
+        match pieces.count_ones() {
+            0 => true,
+            1 => pieces & minor_pieces != 0,
+            _ => {
+                pieces == bishops
+                    && (bishops & bitboard::LIGHT_SQUARES == 0
+                        || bishops & !bitboard::LIGHT_SQUARES == 0)
             }
         }
-        result
+    }
+
+    /// Returns true if a given player could checkmate the opponent by any
+    /// series of legal moves, even with the opponent's help, which decides
+    /// whether running out of time loses the game (FIDE Laws, article 6.9).
+    /// That's impossible with a bare king, with a lone knight against a bare
+    /// king, or if all the pieces besides the kings are bishops standing on
+    /// squares of the same colour.
+    pub fn has_mating_material(&self, player: Player) -> bool {
+        let kings = self.get_both_players(PieceType::King);
+        let pieces = self.occupancy[player as usize] & !kings;
+        let opponent_pieces = self.occupancy[get_opponent(player) as usize] & !kings;
+        let bishops = self.get_both_players(PieceType::Bishop);
+        let lone_knight = opponent_pieces == 0
+            && pieces.count_ones() == 1
+            && pieces & self.pieces[player as usize][PieceType::Knight as usize] != 0;
+        let same_coloured_bishops = pieces | opponent_pieces == bishops
+            && (bishops & bitboard::LIGHT_SQUARES == 0 || bishops & !bitboard::LIGHT_SQUARES == 0);
+        pieces != 0 && !lone_knight && !same_coloured_bishops
+    }
+
+    /// Returns true if a player can claim a draw by the fifty-move rule.
+    pub fn can_claim_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock >= FIFTY_MOVE_RULE_HALFMOVES
+    }
+
+    /// Returns the number of halfmoves since the last capture or pawn move.
+    pub fn get_halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    /// Returns the number of the current full move.
+    pub fn get_fullmove_number(&self) -> u32 {
+        self.fullmove_number
     }
 
     /// Returns true if the current state indicates that the game has finished.
     pub fn is_finished(&self) -> bool {
-        self.get_all_moves().is_empty()
+        !self.get_result().is_ongoing()
+    }
+
+    /// Returns the position of a given player's king, if there is one on the
+    /// board.
+    fn find_king(&self, player: Player) -> Option<Position> {
+        bitboard::squares(self.pieces[player as usize][PieceType::King as usize])
+            .next()
+            .map(bitboard::position)
+    }
+
+    /// Returns true if a given position is attacked by any of the pieces of a
+    /// given player. Pawns only attack diagonally and the square itself may
+    /// be empty, so this can be used for checking castling paths as well.
+    pub fn is_attacked(&self, position: Position, attacker: Player) -> bool {
+        let square = bitboard::square(position);
+        let pieces = &self.pieces[attacker as usize];
+        let occupancy = self.occupancy[0] | self.occupancy[1];
+        let queens = pieces[PieceType::Queen as usize];
+        // A pawn attacks the square if a pawn of the other player standing on
+        // that square would attack the pawn.
+        bitboard::PAWN_ATTACKS[get_opponent(attacker) as usize][square]
+            & pieces[PieceType::Pawn as usize]
+            != 0
+            || bitboard::KNIGHT_ATTACKS[square] & pieces[PieceType::Knight as usize] != 0
+            || bitboard::KING_ATTACKS[square] & pieces[PieceType::King as usize] != 0
+            || bitboard::rook_attacks(square, occupancy)
+                & (pieces[PieceType::Rook as usize] | queens)
+                != 0
+            || bitboard::bishop_attacks(square, occupancy)
+                & (pieces[PieceType::Bishop as usize] | queens)
+                != 0
+    }
+
+    /// Returns true if a given player's king is under check. A board without
+    /// that player's king is never considered to be in check.
+    pub fn is_player_checked(&self, player: Player) -> bool {
+        self.find_king(player)
+            .map(|position| self.is_attacked(position, get_opponent(player)))
+            .unwrap_or(false)
     }
 
     /// Returns true if the current player is under check.
-    fn is_checked(&self) -> bool {
-        let king_position = self
-            .board
-            .iter()
-            .flatten()
-            .find(|field| {
-                field
-                    .as_ref()
-                    .map(|piece| {
-                        piece.get_player() == self.current_player
-                            && (piece.get_type() == PieceType::King)
-                    })
-                    .unwrap_or(false)
-            })
-            .unwrap()
-            .unwrap()
-            .get_position();
-        self.get_all_opponent_moves()
-            .iter()
-            .any(|r#move| r#move.get_end_position() == king_position)
+    pub fn is_checked(&self) -> bool {
+        self.is_player_checked(self.current_player)
     }
 
     /// If a match has resulted in a win, returns the winning player. Otherwise
     /// returns None.
     pub fn get_winner(&self) -> Option<Player> {
-        if self.is_checked() {
-            Some(get_opponent(self.current_player))
-        } else {
-            None
-        }
+        self.get_result().get_winner()
+    }
+    /// Returns the player whose move it is now.
+    pub fn get_current_player(&self) -> Player {
+        self.current_player
+    }
+    /// Returns an iterator over all the pieces on the board.
+    pub fn get_pieces(&self) -> impl Iterator<Item = Piece> + '_ {
+        bitboard::squares(self.occupancy[0] | self.occupancy[1])
+            .filter_map(|square| self.get_piece(bitboard::position(square)))
     }
-    /// Returns a reference to a piece if it exists there, otherwise returns
-    /// None.
-    pub fn get_piece(&self, position: Position) -> Option<&Piece> {
-        let (row, col) = position.as_tuple();
-        self.board[row as usize][col as usize].as_ref()
+    /// Returns the squares occupied by the pieces of a given player and type.
+    pub fn get_piece_bitboard(&self, player: Player, piece_type: PieceType) -> Bitboard {
+        self.pieces[player as usize][piece_type as usize]
+    }
+    /// Returns the squares occupied by the pieces of a given player.
+    pub fn get_occupancy(&self, player: Player) -> Bitboard {
+        self.occupancy[player as usize]
+    }
+    /// Returns the type of the piece standing on a given position, if there
+    /// is one.
+    pub fn get_piece_type(&self, position: Position) -> Option<PieceType> {
+        self.get_piece_kind(bitboard::square(position))
+            .map(|(_, piece_type)| piece_type)
+    }
+    /// Returns the piece standing on a given position, if there is one. The
+    /// first move flag of a pawn and the castling flags of a king and a rook
+    /// follow from the position of the piece and the castling rights.
+    pub fn get_piece(&self, position: Position) -> Option<Piece> {
+        let (player, piece_type) = self.get_piece_kind(bitboard::square(position))?;
+        let flag = match piece_type {
+            PieceType::Pawn => position.get_row() == get_pawn_row(player),
+            PieceType::Rook | PieceType::King => {
+                self.castling_squares & bitboard::bit(bitboard::square(position)) != 0
+            }
+            _ => false,
+        };
+        Some(Piece::new_of_type(piece_type, position, player, flag))
     }
     /// Returns true if a given position resides on the last row from the
     /// perspective of the current player.
     fn is_end_row(&self, position: Position) -> bool {
         let end_row = if self.current_player == Player::White {
-            7 as u8
+            7
         } else {
-            0 as u8
+            0
         };
         position.get_row() == end_row
     }
     /// Returns true if a move would result in a promotion of a pawn.
     pub fn is_promotion_move(&self, r#move: Move) -> bool {
-        let (start_row, start_col) = r#move.get_current_position().as_tuple();
         let end_pos = r#move.get_end_position();
-        let (end_row, col) = end_pos.as_tuple();
-        let moved_piece = &self.board[start_row as usize][start_col as usize];
-        if let Some(Piece::Pawn(_, _)) = moved_piece {
+        if let Some(Piece::Pawn(_, _)) = self.get_piece(r#move.get_current_position()) {
             self.is_end_row(end_pos)
         } else {
             false
         }
     }
+    /// Returns true if a given player can still castle on a given side of
+    /// the board, i.e. their king and the rook in the corner haven't moved.
+    fn can_castle_on_side(&self, player: Player, kingside: bool) -> bool {
+        let squares = bitboard::bit(get_king_square(player))
+            | bitboard::bit(get_rook_square(player, kingside));
+        self.castling_squares & squares == squares
+    }
+    /// Returns the castling rights of both players.
+    pub fn get_castling_rights(&self) -> CastlingRights {
+        CastlingRights {
+            white_kingside: self.can_castle_on_side(Player::White, true),
+            white_queenside: self.can_castle_on_side(Player::White, false),
+            black_kingside: self.can_castle_on_side(Player::Black, true),
+            black_queenside: self.can_castle_on_side(Player::Black, false),
+        }
+    }
+    /// Returns the key identifying the position for repetition detection.
+    pub fn get_position_key(&self) -> PositionKey {
+        self.hash
+    }
     pub fn get_en_passant_square(&self) -> Option<&Position> {
         self.en_passant_square.as_ref()
     }
+    /// Returns true if a move is an en passant capture, i.e. a pawn moves
+    /// diagonally onto an empty square right behind the enemy pawn that has
+    /// just made a double step.
     pub fn can_en_passant(game_state: &GameState, r#move: &Move) -> bool {
-        let (start_row, start_col) = r#move.get_current_position().as_tuple();
-        if let Some(Piece::Pawn(_, can_be_taken_en_passant)) =
-            game_state.board[start_row as usize][start_col as usize]
-        {
-            if can_be_taken_en_passant {
-                let (end_row, end_col) = r#move.get_end_position().as_tuple();
-                if (end_row as i32 - start_row as i32).abs() == 1
-                    && (end_col as i32 - start_col as i32).abs() == 1
-                {
-                    return true;
-                }
-            }
-        }
-        false
+        let start_pos = r#move.get_current_position();
+        let end_pos = r#move.get_end_position();
+        let (start_row, start_col) = start_pos.as_tuple();
+        let (end_row, end_col) = end_pos.as_tuple();
+        let row_shift: i8 = match game_state.get_piece(start_pos) {
+            Some(Piece::Pawn(data, _)) if data.get_player() == Player::White => 1,
+            Some(Piece::Pawn(_, _)) => -1,
+            _ => return false,
+        };
+        let captured_pos = Position::new(start_row, end_col).unwrap();
+        end_row as i8 - start_row as i8 == row_shift
+            && start_col.abs_diff(end_col) == 1
+            && game_state.get_piece(end_pos).is_none()
+            && game_state.get_en_passant_square() == Some(&captured_pos)
     }
 }
 
@@ -292,6 +1072,10 @@ mod tests {
         assert_eq!(moves, *expected_moves);
     }
     fn check_move_not_in_get_moves(state: &GameState, position: &Position, checked_move: &Move) {
+        let moves = state.get_moves(*position);
+        assert!(!moves.contains(checked_move));
+    }
+    fn check_move_in_get_moves(state: &GameState, position: &Position, checked_move: &Move) {
         let moves = state.get_moves(*position);
         assert!(moves.contains(checked_move));
     }
@@ -365,14 +1149,92 @@ mod tests {
         );
         let state = GameState::from_board(board, Player::White, None).unwrap();
         let new_move = make_move!(2, 4, 4, 4);
-        let new_state = GameState::transform_state(&state, new_move, None);
+        let new_state = GameState::transform_state(&state, new_move);
         println!("{:?}", state.get_piece(Position::new(4, 4).unwrap()));
         let result = GameState::update_en_passant(&state, &new_move);
+        assert_eq!(result, Some(make_pos!(4, 4)));
         let mut expected_moves = vec![make_move!(4, 5, 3, 4), make_move!(4, 5, 3, 5)];
         let pos = make_pos!(4, 5);
         test_get_moves(new_state, pos, &mut expected_moves);
     }
     #[test]
+    fn test_en_passant_removes_captured_pawn() {
+        let board = make_board!(
+            Piece::new_pawn(make_pos!(4, 4), Player::White, false),
+            Piece::new_pawn(make_pos!(6, 5), Player::Black, true),
+        );
+        let state = GameState::from_board(board, Player::Black, None).unwrap();
+        let state = GameState::transform_state(&state, make_move!(6, 5, 4, 5));
+        assert_eq!(state.get_en_passant_square(), Some(&make_pos!(4, 5)));
+        let capture = make_move!(4, 4, 5, 5);
+        assert!(GameState::can_en_passant(&state, &capture));
+        check_move_in_get_moves(&state, &make_pos!(4, 4), &capture);
+
+        let new_state = GameState::transform_state(&state, capture);
+        assert!(new_state.get_piece(make_pos!(4, 5)).is_none());
+        assert!(new_state.get_piece(make_pos!(4, 4)).is_none());
+        assert!(matches!(
+            new_state.get_piece(make_pos!(5, 5)),
+            Some(Piece::Pawn(_, _))
+        ));
+        assert!(new_state.get_en_passant_square().is_none());
+    }
+    #[test]
+    fn test_can_en_passant_only_right_after_double_step() {
+        let board = make_board!(
+            Piece::new_pawn(make_pos!(4, 4), Player::White, false),
+            Piece::new_pawn(make_pos!(4, 5), Player::Black, false),
+            Piece::new_pawn(make_pos!(4, 3), Player::Black, false),
+        );
+        let state = GameState::from_board(board, Player::White, Some(make_pos!(4, 5))).unwrap();
+        assert!(GameState::can_en_passant(&state, &make_move!(4, 4, 5, 5)));
+        assert!(!GameState::can_en_passant(&state, &make_move!(4, 4, 5, 3)));
+        assert!(!GameState::can_en_passant(&state, &make_move!(4, 4, 5, 4)));
+        check_move_not_in_get_moves(&state, &make_pos!(4, 4), &make_move!(4, 4, 5, 3));
+    }
+    #[test]
+    fn test_en_passant_black() {
+        let board = make_board!(
+            Piece::new_pawn(make_pos!(1, 0), Player::White, true),
+            Piece::new_pawn(make_pos!(3, 1), Player::Black, false),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        let state = GameState::transform_state(&state, make_move!(1, 0, 3, 0));
+        let capture = make_move!(3, 1, 2, 0);
+        check_move_in_get_moves(&state, &make_pos!(3, 1), &capture);
+        let state = GameState::transform_state(&state, capture);
+        assert!(state.get_piece(make_pos!(3, 0)).is_none());
+        assert!(matches!(
+            state.get_piece(make_pos!(2, 0)),
+            Some(Piece::Pawn(_, _))
+        ));
+    }
+    #[test]
+    fn test_en_passant_exposing_king_is_illegal() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(4, 0), Player::White, false),
+            Piece::new_pawn(make_pos!(4, 4), Player::White, false),
+            Piece::new_pawn(make_pos!(6, 5), Player::Black, true),
+            Piece::new_rook(make_pos!(4, 7), Player::Black, false),
+        );
+        let state = GameState::from_board(board, Player::Black, None).unwrap();
+        let state = GameState::transform_state(&state, make_move!(6, 5, 4, 5));
+        check_move_not_in_get_moves(&state, &make_pos!(4, 4), &make_move!(4, 4, 5, 5));
+    }
+    #[test]
+    fn test_update_en_passant_edge_columns() {
+        let board = make_board!(
+            Piece::new_pawn(make_pos!(1, 0), Player::White, true),
+            Piece::new_pawn(make_pos!(1, 7), Player::White, true),
+            Piece::new_pawn(make_pos!(3, 1), Player::Black, false),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        let result = GameState::update_en_passant(&state, &make_move!(1, 0, 3, 0));
+        assert_eq!(result, Some(make_pos!(3, 0)));
+        let result = GameState::update_en_passant(&state, &make_move!(1, 7, 3, 7));
+        assert_eq!(result, None);
+    }
+    #[test]
     fn test_knight_moves_center() {
         let board = make_board!(Piece::new_knight(make_pos!(4, 3), Player::White),);
         let state = GameState::from_board(board, Player::White, None).unwrap();
@@ -658,6 +1520,7 @@ mod tests {
         );
         let state = GameState::from_board(board, Player::White, None).unwrap();
         let pos = make_pos!(3, 3);
+        // (2, 3) and (4, 3) are attacked by both bishops.
         let mut expected_moves = vec![
             make_move!(3, 3, 2, 2),
             make_move!(3, 3, 4, 4),
@@ -665,8 +1528,6 @@ mod tests {
             make_move!(3, 3, 2, 4),
             make_move!(3, 3, 3, 2),
             make_move!(3, 3, 3, 4),
-            make_move!(3, 3, 2, 3),
-            make_move!(3, 3, 4, 3),
         ];
         test_get_moves(state, pos, &mut expected_moves);
     }
@@ -690,6 +1551,81 @@ mod tests {
         test_get_moves(state, pos, &mut expected_moves);
     }
 
+    #[test]
+    fn test_pinned_piece_cant_move() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, false),
+            Piece::new_knight(make_pos!(1, 4), Player::White),
+            Piece::new_rook(make_pos!(7, 4), Player::Black, false),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        let pos = make_pos!(1, 4);
+        let mut expected_moves = vec![];
+        test_get_moves(state, pos, &mut expected_moves);
+    }
+    #[test]
+    fn test_pinned_piece_moves_along_pin() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, false),
+            Piece::new_rook(make_pos!(2, 4), Player::White, false),
+            Piece::new_rook(make_pos!(4, 4), Player::Black, false),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        let pos = make_pos!(2, 4);
+        let mut expected_moves = vec![
+            make_move!(2, 4, 1, 4),
+            make_move!(2, 4, 3, 4),
+            make_move!(2, 4, 4, 4),
+        ];
+        test_get_moves(state, pos, &mut expected_moves);
+    }
+    #[test]
+    fn test_must_resolve_check() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, false),
+            Piece::new_rook(make_pos!(1, 0), Player::White, false),
+            Piece::new_queen(make_pos!(4, 4), Player::Black),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        let pos = make_pos!(1, 0);
+        let mut expected_moves = vec![make_move!(1, 0, 1, 4)];
+        test_get_moves(state, pos, &mut expected_moves);
+    }
+    #[test]
+    fn test_king_cant_walk_into_check() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, false),
+            Piece::new_rook(make_pos!(7, 3), Player::Black, false),
+            Piece::new_pawn(make_pos!(2, 6), Player::Black, false),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        let pos = make_pos!(0, 4);
+        let mut expected_moves = vec![make_move!(0, 4, 1, 4), make_move!(0, 4, 0, 5)];
+        test_get_moves(state, pos, &mut expected_moves);
+    }
+    #[test]
+    fn test_no_moves_for_opponent_pieces() {
+        let board = make_board!(Piece::new_knight(make_pos!(4, 3), Player::Black));
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        let pos = make_pos!(4, 3);
+        let mut expected_moves = vec![];
+        test_get_moves(state, pos, &mut expected_moves);
+    }
+    #[test]
+    fn test_is_attacked() {
+        let board = make_board!(
+            Piece::new_pawn(make_pos!(3, 3), Player::White, false),
+            Piece::new_bishop(make_pos!(0, 0), Player::Black),
+            Piece::new_knight(make_pos!(1, 1), Player::White),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        assert!(state.is_attacked(make_pos!(4, 2), Player::White));
+        assert!(state.is_attacked(make_pos!(4, 4), Player::White));
+        assert!(!state.is_attacked(make_pos!(4, 3), Player::White));
+        assert!(state.is_attacked(make_pos!(1, 1), Player::Black));
+        assert!(!state.is_attacked(make_pos!(2, 2), Player::Black));
+        assert!(state.is_attacked(make_pos!(3, 2), Player::White));
+    }
     #[test]
     fn test_is_player_checked() {
         let board = make_board!(
@@ -706,30 +1642,42 @@ mod tests {
         let board = make_board!(Piece::new_bishop(make_pos!(2, 2), Player::Black),);
         let state = GameState::from_board(board, Player::White, None).unwrap();
         let new_move = make_move!(2, 2, 3, 3);
-        let promotion = None;
 
-        let new_state = GameState::transform_state(&state, new_move, promotion);
+        let new_state = GameState::transform_state(&state, new_move);
 
-        assert!(new_state.board[2][2].is_none());
-        assert!(matches!(new_state.board[3][3], Some(Piece::Bishop(_))));
+        assert!(new_state.get_piece(make_pos!(2, 2)).is_none());
+        assert!(matches!(
+            new_state.get_piece(make_pos!(3, 3)),
+            Some(Piece::Bishop(_))
+        ));
 
         assert_eq!(new_state.current_player, get_opponent(state.current_player));
 
         assert!(new_state.en_passant_square.is_none());
+
+        // Only pawns can be promoted.
+        let new_move = new_move.with_promotion(Some(PromotionType::Knight));
+        let new_state = GameState::transform_state(&state, new_move);
+        assert!(matches!(
+            new_state.get_piece(make_pos!(3, 3)),
+            Some(Piece::Bishop(_))
+        ));
     }
 
     #[test]
     fn test_transform_state_with_promotion() {
-        let board = make_board!(Piece::new_bishop(make_pos!(2, 2), Player::Black),);
+        let board = make_board!(Piece::new_pawn(make_pos!(6, 2), Player::White, false),);
         let state = GameState::from_board(board, Player::White, None).unwrap();
-        let new_move = make_move!(2, 2, 3, 3);
-        let promotion = Some(PromotionType::Knight);
+        let new_move = make_move!(6, 2, 7, 2).with_promotion(Some(PromotionType::Knight));
 
-        let new_state = GameState::transform_state(&state, new_move, promotion);
+        let new_state = GameState::transform_state(&state, new_move);
 
         // Sprawdź, czy pionek został przeniesiony
-        assert!(new_state.board[2][2].is_none());
-        assert!(matches!(new_state.board[3][3], Some(Piece::Knight(_))));
+        assert!(new_state.get_piece(make_pos!(6, 2)).is_none());
+        assert!(matches!(
+            new_state.get_piece(make_pos!(7, 2)),
+            Some(Piece::Knight(_))
+        ));
 
         // Sprawdź, czy aktualny gracz został zmieniony
         assert_eq!(new_state.current_player, get_opponent(state.current_player));
@@ -841,6 +1789,117 @@ mod tests {
         check_move_not_in_get_moves(&state, &pos, &make_move!(0, 4, 0, 6));
     }
     #[test]
+    fn test_king_cant_castle_through_check() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, true),
+            Piece::new_rook(make_pos!(0, 0), Player::White, true),
+            Piece::new_rook(make_pos!(0, 7), Player::White, true),
+            Piece::new_rook(make_pos!(7, 5), Player::Black, false),
+            Piece::new_bishop(make_pos!(3, 6), Player::Black),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        let pos = make_pos!(0, 4);
+        // The rook attacks (0, 5), the bishop attacks (0, 3).
+        check_move_not_in_get_moves(&state, &pos, &make_move!(0, 4, 0, 6));
+        check_move_not_in_get_moves(&state, &pos, &make_move!(0, 4, 0, 2));
+    }
+    #[test]
+    fn test_castling_moves_rook() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, true),
+            Piece::new_rook(make_pos!(0, 0), Player::White, true),
+            Piece::new_rook(make_pos!(0, 7), Player::White, true),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        let short = GameState::transform_state(&state, make_move!(0, 4, 0, 6));
+        assert!(short.get_piece(make_pos!(0, 7)).is_none());
+        assert!(matches!(
+            short.get_piece(make_pos!(0, 5)),
+            Some(Piece::Rook(_, false))
+        ));
+        assert!(matches!(
+            short.get_piece(make_pos!(0, 6)),
+            Some(Piece::King(_, false))
+        ));
+        assert!(matches!(
+            short.get_piece(make_pos!(0, 0)),
+            Some(Piece::Rook(_, true))
+        ));
+        assert_eq!(
+            short.get_piece(make_pos!(0, 5)).unwrap().get_position(),
+            make_pos!(0, 5)
+        );
+
+        let long = GameState::transform_state(&state, make_move!(0, 4, 0, 2));
+        assert!(long.get_piece(make_pos!(0, 0)).is_none());
+        assert!(long.get_piece(make_pos!(0, 1)).is_none());
+        assert!(matches!(
+            long.get_piece(make_pos!(0, 3)),
+            Some(Piece::Rook(_, false))
+        ));
+        assert!(matches!(
+            long.get_piece(make_pos!(0, 2)),
+            Some(Piece::King(_, false))
+        ));
+    }
+    #[test]
+    fn test_black_castling_moves_rook() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(7, 4), Player::Black, true),
+            Piece::new_rook(make_pos!(7, 7), Player::Black, true),
+        );
+        let state = GameState::from_board(board, Player::Black, None).unwrap();
+        check_move_in_get_moves(&state, &make_pos!(7, 4), &make_move!(7, 4, 7, 6));
+        let new_state = GameState::transform_state(&state, make_move!(7, 4, 7, 6));
+        assert!(new_state.get_piece(make_pos!(7, 7)).is_none());
+        assert!(matches!(
+            new_state.get_piece(make_pos!(7, 5)),
+            Some(Piece::Rook(_, false))
+        ));
+    }
+    #[test]
+    fn test_castling_rights_revoked_after_moves() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, true),
+            Piece::new_rook(make_pos!(0, 0), Player::White, true),
+            Piece::new_rook(make_pos!(0, 7), Player::White, true),
+            Piece::new_king(make_pos!(7, 4), Player::Black, false),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        let state = GameState::transform_state(&state, make_move!(0, 7, 1, 7));
+        let state = GameState::transform_state(&state, make_move!(7, 4, 7, 3));
+        let state = GameState::transform_state(&state, make_move!(1, 7, 0, 7));
+        let state = GameState::transform_state(&state, make_move!(7, 3, 7, 4));
+        assert!(matches!(
+            state.get_piece(make_pos!(0, 7)),
+            Some(Piece::Rook(_, false))
+        ));
+        check_move_not_in_get_moves(&state, &make_pos!(0, 4), &make_move!(0, 4, 0, 6));
+        check_move_in_get_moves(&state, &make_pos!(0, 4), &make_move!(0, 4, 0, 2));
+
+        let state = GameState::transform_state(&state, make_move!(0, 4, 1, 4));
+        let state = GameState::transform_state(&state, make_move!(7, 4, 7, 3));
+        let state = GameState::transform_state(&state, make_move!(1, 4, 0, 4));
+        let state = GameState::transform_state(&state, make_move!(7, 3, 7, 4));
+        assert!(matches!(
+            state.get_piece(make_pos!(0, 4)),
+            Some(Piece::King(_, false))
+        ));
+        check_move_not_in_get_moves(&state, &make_pos!(0, 4), &make_move!(0, 4, 0, 2));
+    }
+    #[test]
+    fn test_cant_castle_with_captured_rook() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, true),
+            Piece::new_rook(make_pos!(0, 7), Player::White, true),
+            Piece::new_king(make_pos!(7, 0), Player::Black, false),
+            Piece::new_knight(make_pos!(2, 6), Player::Black),
+        );
+        let state = GameState::from_board(board, Player::Black, None).unwrap();
+        let state = GameState::transform_state(&state, make_move!(2, 6, 0, 7));
+        check_move_not_in_get_moves(&state, &make_pos!(0, 4), &make_move!(0, 4, 0, 6));
+    }
+    #[test]
     fn test_is_promotion() {
         let board = make_board!(
             Piece::new_pawn(make_pos!(6, 4), Player::White, false),
@@ -883,8 +1942,9 @@ mod tests {
             Piece::new_pawn(make_pos!(1, 4), Player::Black, false),
         );
         let state = GameState::from_board(board, Player::White, None).unwrap();
-        // assert!(state.is_finished());
+        assert!(state.is_finished());
         assert!(state.get_winner().is_none());
+        assert_eq!(state.get_result(), GameResult::Stalemate);
     }
     #[test]
     fn test_checkmate() {
@@ -894,9 +1954,371 @@ mod tests {
             Piece::new_queen(make_pos!(1, 4), Player::Black),
         );
         let state = GameState::from_board(board, Player::White, None).unwrap();
-        // assert!(state.is_finished());
+        assert!(state.is_finished());
         assert!(state.get_winner().is_some());
         assert!(state.get_winner().unwrap() == Player::Black);
+        assert_eq!(state.get_result(), GameResult::Checkmate(Player::Black));
+    }
+    #[test]
+    fn test_castling_rights() {
+        let state = GameState::init();
+        let all = CastlingRights {
+            white_kingside: true,
+            white_queenside: true,
+            black_kingside: true,
+            black_queenside: true,
+        };
+        assert_eq!(state.get_castling_rights(), all);
+        let state = GameState::transform_state(&state, make_move!(1, 7, 3, 7));
+        let state = GameState::transform_state(&state, make_move!(6, 4, 4, 4));
+        let state = GameState::transform_state(&state, make_move!(0, 7, 1, 7));
+        let state = GameState::transform_state(&state, make_move!(7, 4, 6, 4));
+        assert_eq!(
+            state.get_castling_rights(),
+            CastlingRights {
+                white_kingside: false,
+                black_kingside: false,
+                black_queenside: false,
+                ..all
+            }
+        );
+    }
+    #[test]
+    fn test_position_key() {
+        let state = GameState::init();
+        let key = state.get_position_key();
+        let state = GameState::transform_state(&state, make_move!(0, 6, 2, 5));
+        assert_ne!(state.get_position_key(), key);
+        let state = GameState::transform_state(&state, make_move!(7, 6, 5, 5));
+        let state = GameState::transform_state(&state, make_move!(2, 5, 0, 6));
+        let state = GameState::transform_state(&state, make_move!(5, 5, 7, 6));
+        assert_eq!(state.get_position_key(), key);
+        // Moving the king back and forth loses the castling rights.
+        let state = GameState::transform_state(&state, make_move!(1, 4, 2, 4));
+        let state = GameState::transform_state(&state, make_move!(6, 4, 5, 4));
+        let before = state.get_position_key();
+        let state = GameState::transform_state(&state, make_move!(0, 4, 1, 4));
+        let state = GameState::transform_state(&state, make_move!(7, 4, 6, 4));
+        let state = GameState::transform_state(&state, make_move!(1, 4, 0, 4));
+        let state = GameState::transform_state(&state, make_move!(6, 4, 7, 4));
+        assert_ne!(state.get_position_key(), before);
+    }
+    #[test]
+    fn test_insufficient_material_kings_only() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, false),
+            Piece::new_king(make_pos!(7, 4), Player::Black, false),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        assert!(state.has_insufficient_material());
+        assert_eq!(state.get_result(), GameResult::DrawByInsufficientMaterial);
+    }
+    #[test]
+    fn test_insufficient_material_single_minor_piece() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, false),
+            Piece::new_king(make_pos!(7, 4), Player::Black, false),
+            Piece::new_knight(make_pos!(3, 3), Player::Black),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        assert_eq!(state.get_result(), GameResult::DrawByInsufficientMaterial);
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, false),
+            Piece::new_king(make_pos!(7, 4), Player::Black, false),
+            Piece::new_bishop(make_pos!(3, 3), Player::White),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        assert_eq!(state.get_result(), GameResult::DrawByInsufficientMaterial);
+    }
+    #[test]
+    fn test_insufficient_material_same_coloured_bishops() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, false),
+            Piece::new_king(make_pos!(7, 4), Player::Black, false),
+            Piece::new_bishop(make_pos!(3, 3), Player::White),
+            Piece::new_bishop(make_pos!(5, 1), Player::Black),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        assert_eq!(state.get_result(), GameResult::DrawByInsufficientMaterial);
+    }
+    #[test]
+    fn test_mating_material() {
+        let state = GameState::from_fen("4k3/8/8/8/8/8/8/4K2N w - - 0 1").unwrap();
+        assert!(!state.has_mating_material(Player::White));
+        assert!(!state.has_mating_material(Player::Black));
+        let state = GameState::from_fen("4k3/7p/8/8/8/8/8/2B1K2N w - - 0 1").unwrap();
+        assert!(state.has_mating_material(Player::White));
+        assert!(state.has_mating_material(Player::Black));
+        // A lone minor piece can mate with the help of the opponent's pieces,
+        // which can block the escape squares of their king.
+        let state = GameState::from_fen("4k3/4p3/8/8/8/8/8/4K2N w - - 0 1").unwrap();
+        assert!(state.has_mating_material(Player::White));
+        let state = GameState::from_fen("4k3/8/8/8/8/8/8/n3K2B w - - 0 1").unwrap();
+        assert!(state.has_mating_material(Player::White));
+        assert!(state.has_mating_material(Player::Black));
+        // Bishops on squares of the same colour never can.
+        let state = GameState::from_fen("4k3/8/8/8/8/8/2b5/4KB2 w - - 0 1").unwrap();
+        assert!(!state.has_mating_material(Player::White));
+        assert!(!state.has_mating_material(Player::Black));
+    }
+    #[test]
+    fn test_sufficient_material() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, false),
+            Piece::new_king(make_pos!(7, 4), Player::Black, false),
+            Piece::new_bishop(make_pos!(3, 3), Player::White),
+            Piece::new_bishop(make_pos!(5, 2), Player::Black),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        assert!(!state.has_insufficient_material());
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, false),
+            Piece::new_king(make_pos!(7, 4), Player::Black, false),
+            Piece::new_knight(make_pos!(3, 3), Player::White),
+            Piece::new_knight(make_pos!(3, 4), Player::White),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        assert!(!state.has_insufficient_material());
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, false),
+            Piece::new_king(make_pos!(7, 4), Player::Black, false),
+            Piece::new_pawn(make_pos!(3, 3), Player::White, false),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        assert!(!state.has_insufficient_material());
+        assert_eq!(state.get_result(), GameResult::Ongoing);
+    }
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    const KIWIPETE_FEN: &str =
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn test_fen_init() {
+        assert_eq!(GameState::init().to_fen(), START_FEN);
+        let state = GameState::from_fen(START_FEN).unwrap();
+        assert_eq!(
+            state.get_position_key(),
+            GameState::init().get_position_key()
+        );
+        assert!(matches!(
+            state.get_piece(make_pos!(1, 3)),
+            Some(Piece::Pawn(_, true))
+        ));
+        assert!(matches!(
+            state.get_piece(make_pos!(0, 4)),
+            Some(Piece::King(_, true))
+        ));
+        assert!(matches!(
+            state.get_piece(make_pos!(7, 0)),
+            Some(Piece::Rook(_, true))
+        ));
+    }
+    #[test]
+    fn test_fen_round_trip() {
+        for fen in [
+            KIWIPETE_FEN,
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 3",
+        ] {
+            assert_eq!(GameState::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+    #[test]
+    fn test_fen_after_moves() {
+        let state = GameState::init();
+        let state = GameState::transform_state(&state, make_move!(1, 4, 3, 4));
+        assert_eq!(
+            state.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+        );
+        let state = GameState::transform_state(&state, make_move!(6, 2, 4, 2));
+        let state = GameState::transform_state(&state, make_move!(3, 4, 4, 4));
+        let state = GameState::transform_state(&state, make_move!(6, 3, 4, 3));
+        assert_eq!(
+            state.to_fen(),
+            "rnbqkbnr/pp2pppp/8/2ppP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3"
+        );
+        let state = GameState::transform_state(&state, make_move!(0, 4, 1, 4));
+        assert_eq!(
+            state.to_fen(),
+            "rnbqkbnr/pp2pppp/8/2ppP3/8/8/PPPPKPPP/RNBQ1BNR b kq - 1 3"
+        );
+    }
+    #[test]
+    fn test_fen_castling_flags() {
+        let state = GameState::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1").unwrap();
+        assert!(matches!(
+            state.get_piece(make_pos!(0, 4)),
+            Some(Piece::King(_, true))
+        ));
+        assert!(matches!(
+            state.get_piece(make_pos!(0, 7)),
+            Some(Piece::Rook(_, true))
+        ));
+        assert!(matches!(
+            state.get_piece(make_pos!(0, 0)),
+            Some(Piece::Rook(_, false))
+        ));
+        assert!(matches!(
+            state.get_piece(make_pos!(7, 7)),
+            Some(Piece::Rook(_, false))
+        ));
+        assert!(matches!(
+            state.get_piece(make_pos!(7, 0)),
+            Some(Piece::Rook(_, true))
+        ));
+        check_move_in_get_moves(&state, &make_pos!(0, 4), &make_move!(0, 4, 0, 6));
+        check_move_not_in_get_moves(&state, &make_pos!(0, 4), &make_move!(0, 4, 0, 2));
+    }
+    #[test]
+    fn test_fen_pawn_first_move() {
+        let state = GameState::from_fen("4k3/3p4/8/8/8/P7/1P6/4K3 w - - 0 1").unwrap();
+        assert!(matches!(
+            state.get_piece(make_pos!(1, 1)),
+            Some(Piece::Pawn(_, true))
+        ));
+        assert!(matches!(
+            state.get_piece(make_pos!(2, 0)),
+            Some(Piece::Pawn(_, false))
+        ));
+        assert!(matches!(
+            state.get_piece(make_pos!(6, 3)),
+            Some(Piece::Pawn(_, true))
+        ));
+    }
+    #[test]
+    fn test_fen_errors() {
+        for (fen, error) in [
+            (
+                "8/8/8/8/8/8/8/8 w - - 0",
+                "FEN must consist of 6 fields, found 5",
+            ),
+            (
+                "4k3/8/8/8/8/8/4K3 w - - 0 1",
+                "Piece placement must describe 8 ranks, found 7",
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K2 w - - 0 1",
+                "Rank 1 describes 7 squares instead of 8",
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3p w - - 0 1",
+                "Rank 1 describes more than 8 squares",
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4X3 w - - 0 1",
+                "Invalid piece symbol 'X' in rank 1",
+            ),
+            ("4k3/8/8/8/8/8/8/4K3 x - - 0 1", "Invalid active color 'x'"),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w KX - 0 1",
+                "Invalid castling availability 'KX'",
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w K - 0 1",
+                "Castling right 'K' requires an unmoved king and rook",
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - e3 0 1",
+                "Invalid en passant target square 'e3'",
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - z9 0 1",
+                "Invalid square name: 'z9'",
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - x 1",
+                "Invalid halfmove clock 'x'",
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - 0 0",
+                "Invalid fullmove number '0'",
+            ),
+            (
+                "8/8/8/8/8/8/8/4K3 w - - 0 1",
+                "There must be exactly one Black king",
+            ),
+            (
+                "4k3/4R3/8/8/8/8/8/4K3 w - - 0 1",
+                "The player who is not to move can't be in check",
+            ),
+        ] {
+            assert_eq!(
+                GameState::from_fen(fen).err(),
+                Some(error.to_string()),
+                "{}",
+                fen
+            );
+        }
+    }
+    #[test]
+    fn test_move_counters() {
+        let state = GameState::init();
+        let state = GameState::transform_state(&state, make_move!(0, 6, 2, 5));
+        assert_eq!(state.get_halfmove_clock(), 1);
+        assert_eq!(state.get_fullmove_number(), 1);
+        let state = GameState::transform_state(&state, make_move!(7, 6, 5, 5));
+        assert_eq!(state.get_halfmove_clock(), 2);
+        assert_eq!(state.get_fullmove_number(), 2);
+        let state = GameState::transform_state(&state, make_move!(1, 4, 3, 4));
+        assert_eq!(state.get_halfmove_clock(), 0);
+        assert_eq!(state.get_fullmove_number(), 2);
+        let state = GameState::transform_state(&state, make_move!(5, 5, 3, 4));
+        assert_eq!(state.get_halfmove_clock(), 0);
+        assert_eq!(state.get_fullmove_number(), 3);
+    }
+    #[test]
+    fn test_fifty_move_rule() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, false),
+            Piece::new_king(make_pos!(7, 4), Player::Black, false),
+            Piece::new_rook(make_pos!(0, 0), Player::White, false),
+        );
+        let mut state = GameState::from_board(board, Player::White, None).unwrap();
+        state.halfmove_clock = 99;
+        assert!(!state.can_claim_fifty_move_draw());
+        let state = GameState::transform_state(&state, make_move!(0, 0, 1, 0));
+        assert!(state.can_claim_fifty_move_draw());
+        assert_eq!(state.get_result(), GameResult::Ongoing);
+    }
+    #[test]
+    fn test_seventy_five_move_rule() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, false),
+            Piece::new_king(make_pos!(7, 4), Player::Black, false),
+            Piece::new_rook(make_pos!(0, 0), Player::White, false),
+        );
+        let mut state = GameState::from_board(board, Player::White, None).unwrap();
+        state.halfmove_clock = 149;
+        let state = GameState::transform_state(&state, make_move!(0, 0, 1, 0));
+        assert_eq!(state.get_result(), GameResult::DrawByFiftyMoves);
+        assert!(state.is_finished());
+    }
+    #[test]
+    fn test_checkmate_beats_seventy_five_move_rule() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, false),
+            Piece::new_king(make_pos!(2, 4), Player::Black, false),
+            Piece::new_queen(make_pos!(1, 4), Player::Black),
+        );
+        let mut state = GameState::from_board(board, Player::White, None).unwrap();
+        state.halfmove_clock = 150;
+        assert_eq!(state.get_result(), GameResult::Checkmate(Player::Black));
+    }
+    #[test]
+    fn test_check_is_not_a_win() {
+        let board = make_board!(
+            Piece::new_king(make_pos!(0, 4), Player::White, false),
+            Piece::new_king(make_pos!(7, 0), Player::Black, false),
+            Piece::new_rook(make_pos!(5, 4), Player::Black, false),
+        );
+        let state = GameState::from_board(board, Player::White, None).unwrap();
+        assert!(state.is_checked());
+        assert!(!state.is_finished());
+        assert!(state.get_winner().is_none());
+        assert_eq!(state.get_result(), GameResult::Ongoing);
     }
 
     #[test]
@@ -909,14 +2331,175 @@ mod tests {
 
         let new_move = make_move!(1, 6, 3, 6);
 
-        let new_state = GameState::transform_state(&state, new_move, None);
+        let new_state = GameState::transform_state(&state, new_move);
 
         let new_move2 = make_move!(6, 5, 4, 5);
 
-        let new_state2 = GameState::transform_state(&new_state, new_move2, None);
+        let new_state2 = GameState::transform_state(&new_state, new_move2);
 
         let pos = make_pos!(3, 6);
         let mut expected_moves = vec![make_move!(3, 6, 4, 6), make_move!(3, 6, 4, 5)];
         test_get_moves(new_state2, pos, &mut expected_moves);
     }
+
+    #[test]
+    fn test_perft_reference_positions() {
+        // The positions and their node counts come from the Chess Programming
+        // Wiki; the depths are kept low for the tests to run quickly.
+        let positions: [(&str, &[u64]); 6] = [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                &[20, 400, 8902, 197281],
+            ),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                &[48, 2039, 97862],
+            ),
+            (
+                "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+                &[14, 191, 2812, 43238],
+            ),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                &[6, 264, 9467],
+            ),
+            (
+                "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                &[44, 1486, 62379],
+            ),
+            (
+                "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+                &[46, 2079, 89890],
+            ),
+        ];
+        for (fen, node_counts) in positions {
+            let state = GameState::from_fen(fen).unwrap();
+            for (depth, &nodes) in (1..).zip(node_counts) {
+                assert_eq!(state.perft(depth), nodes, "depth {} of {}", depth, fen);
+            }
+        }
+    }
+    #[test]
+    fn test_perft_divide() {
+        let state = GameState::init();
+        let divided = state.perft_divide(3);
+        assert_eq!(divided.len(), 20);
+        assert_eq!(divided.iter().map(|(_, nodes)| nodes).sum::<u64>(), 8902);
+        let e4 = Move::from_uci("e2e4").unwrap();
+        assert_eq!(
+            divided.iter().find(|(r#move, _)| *r#move == e4).unwrap().1,
+            600
+        );
+        assert_eq!(state.perft(0), 1);
+    }
+    #[test]
+    fn test_zobrist_hash_reference_keys() {
+        // The keys come from the description of the Polyglot book format.
+        let positions = [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                0x463b96181691fc9c,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+                0x823c9b50fd114196,
+            ),
+            (
+                "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2",
+                0x0756b94461c50fb0,
+            ),
+            (
+                "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2",
+                0x662fafb965db29d4,
+            ),
+            (
+                "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+                0x22a48b5a8e47ff78,
+            ),
+            (
+                "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 0 3",
+                0x652a607ca3f242c1,
+            ),
+            (
+                "rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - - 0 4",
+                0x00fdd303c946bdd9,
+            ),
+            (
+                "rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3",
+                0x3c8123ea7b067637,
+            ),
+            (
+                "rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4",
+                0x5c3f9b829b279560,
+            ),
+        ];
+        for (fen, key) in positions {
+            assert_eq!(GameState::from_fen(fen).unwrap().get_position_key(), key);
+        }
+        // The same keys are reached by playing the moves.
+        let mut state = GameState::init();
+        for (uci, key) in [
+            ("e2e4", 0x823c9b50fd114196),
+            ("d7d5", 0x0756b94461c50fb0),
+            ("e4e5", 0x662fafb965db29d4),
+            ("f7f5", 0x22a48b5a8e47ff78),
+            ("e1e2", 0x652a607ca3f242c1),
+            ("e8f7", 0x00fdd303c946bdd9),
+        ] {
+            state = GameState::transform_state(&state, Move::from_uci(uci).unwrap());
+            assert_eq!(state.get_position_key(), key);
+        }
+    }
+    #[test]
+    fn test_zobrist_hash_is_incremental() {
+        // Kiwipete covers castling, promotions and en passant captures.
+        let state = GameState::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        for first in state.get_all_moves() {
+            let next = GameState::transform_state(&state, first);
+            assert_eq!(next.get_position_key(), next.compute_hash());
+            for second in next.get_all_moves() {
+                let last = GameState::transform_state(&next, second);
+                assert_eq!(last.get_position_key(), last.compute_hash());
+            }
+        }
+    }
+    #[test]
+    fn test_make_unmake_move() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            // Black to move, with an en passant capture available.
+            "rnbqkbnr/ppp1p1pp/8/8/3pPp2/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3",
+        ];
+        for fen in fens {
+            let state = GameState::from_fen(fen).unwrap();
+            for first in state.get_all_moves() {
+                let mut current = state.clone();
+                let first_undo = current.make_move(first);
+                let next = GameState::transform_state(&state, first);
+                assert_eq!(current, next, "{} in {}", first.to_uci(), fen);
+                for second in next.get_all_moves() {
+                    let second_undo = current.make_move(second);
+                    current.unmake_move(second_undo);
+                    assert_eq!(
+                        current,
+                        next,
+                        "{} {} in {}",
+                        first.to_uci(),
+                        second.to_uci(),
+                        fen
+                    );
+                }
+                current.unmake_move(first_undo);
+                assert_eq!(current, state, "{} in {}", first.to_uci(), fen);
+            }
+        }
+    }
 }
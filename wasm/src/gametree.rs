@@ -0,0 +1,418 @@
+use crate::gamestate::{GameState, PositionKey};
+use crate::pgn::MovetextElement;
+use crate::player::Player;
+use crate::Move;
+
+/// An index of a node in a game tree.
+pub type NodeId = usize;
+
+/// The node holding the initial position of the game.
+pub const ROOT: NodeId = 0;
+
+/// A position in the game tree along with the move leading to it.
+#[derive(Clone)]
+struct Node {
+    parent: Option<NodeId>,
+    /// The move leading to the position; None for the root.
+    chess_move: Option<Move>,
+    /// The move written in SAN; empty for the root.
+    san: String,
+    state: GameState,
+    /// The number of moves leading to the position from the initial one.
+    ply: usize,
+    /// The comments written before the move, which may only happen at the
+    /// start of a variation.
+    starting_comments: Vec<String>,
+    /// The comments written after the move; for the root, the ones before the
+    /// first move of the game.
+    comments: Vec<String>,
+    nags: Vec<u8>,
+    /// The continuations of the position; the first one is the main one and
+    /// the others are its variations.
+    children: Vec<NodeId>,
+    /// The index of the continuation that the line going through the node
+    /// follows, e.g. when a move is redone.
+    selected_child: usize,
+}
+
+/// A game along with its variations. The nodes are kept in an arena; the
+/// nodes of deleted branches stay there, but can no longer be reached.
+#[derive(Clone)]
+pub struct GameTree {
+    nodes: Vec<Node>,
+}
+
+impl GameTree {
+    /// Creates a tree of a game starting from a given state.
+    pub fn new(state: GameState) -> Self {
+        Self {
+            nodes: vec![Node {
+                parent: None,
+                chess_move: None,
+                san: String::new(),
+                state,
+                ply: 0,
+                starting_comments: vec![],
+                comments: vec![],
+                nags: vec![],
+                children: vec![],
+                selected_child: 0,
+            }],
+        }
+    }
+
+    pub fn get_state(&self, node: NodeId) -> &GameState {
+        &self.nodes[node].state
+    }
+    pub fn get_position_key(&self, node: NodeId) -> PositionKey {
+        self.get_state(node).get_position_key()
+    }
+    pub fn get_move(&self, node: NodeId) -> Option<Move> {
+        self.nodes[node].chess_move
+    }
+    pub fn get_san(&self, node: NodeId) -> &str {
+        &self.nodes[node].san
+    }
+    pub fn get_ply(&self, node: NodeId) -> usize {
+        self.nodes[node].ply
+    }
+    pub fn get_parent(&self, node: NodeId) -> Option<NodeId> {
+        self.nodes[node].parent
+    }
+    /// Returns the continuations of a position, the main one first.
+    pub fn get_children(&self, node: NodeId) -> &[NodeId] {
+        &self.nodes[node].children
+    }
+    pub fn get_comments(&self, node: NodeId) -> &[String] {
+        &self.nodes[node].comments
+    }
+    pub fn add_comment(&mut self, node: NodeId, comment: &str) {
+        self.nodes[node].comments.push(comment.to_string());
+    }
+    /// Removes all the comments of a node, including those written before
+    /// its move.
+    pub fn clear_comments(&mut self, node: NodeId) {
+        self.nodes[node].starting_comments.clear();
+        self.nodes[node].comments.clear();
+    }
+    pub fn get_nags(&self, node: NodeId) -> &[u8] {
+        &self.nodes[node].nags
+    }
+    /// Adds a NAG to a node, unless it already has it.
+    pub fn add_nag(&mut self, node: NodeId, nag: u8) {
+        if !self.nodes[node].nags.contains(&nag) {
+            self.nodes[node].nags.push(nag);
+        }
+    }
+    pub fn remove_nag(&mut self, node: NodeId, nag: u8) {
+        self.nodes[node].nags.retain(|&other| other != nag);
+    }
+
+    /// Adds a move made in the position of a given node and selects it as
+    /// the continuation of the line. If the move has already been made there,
+    /// the existing node is returned; otherwise the move becomes the main
+    /// continuation, or a variation if there already is one. The move is
+    /// assumed to be legal.
+    pub fn add_move(&mut self, node: NodeId, chess_move: Move) -> NodeId {
+        if let Some(idx) = self.nodes[node]
+            .children
+            .iter()
+            .position(|&child| self.nodes[child].chess_move == Some(chess_move))
+        {
+            self.nodes[node].selected_child = idx;
+            return self.nodes[node].children[idx];
+        }
+        let state = &self.nodes[node].state;
+        let san = chess_move.to_san(state);
+        let new_state = GameState::transform_state(state, chess_move);
+        let child = self.nodes.len();
+        self.nodes.push(Node {
+            parent: Some(node),
+            chess_move: Some(chess_move),
+            san,
+            state: new_state,
+            ply: self.nodes[node].ply + 1,
+            starting_comments: vec![],
+            comments: vec![],
+            nags: vec![],
+            children: vec![],
+            selected_child: 0,
+        });
+        let parent = &mut self.nodes[node];
+        parent.children.push(child);
+        parent.selected_child = parent.children.len() - 1;
+        child
+    }
+
+    /// Returns the continuation which the line going through a node follows.
+    pub fn get_selected_child(&self, node: NodeId) -> Option<NodeId> {
+        let node = &self.nodes[node];
+        node.children.get(node.selected_child).copied()
+    }
+    /// Makes a node the continuation followed by the line going through its
+    /// parent, as well as by all the lines leading to it.
+    pub fn select(&mut self, node: NodeId) {
+        let mut current = node;
+        while let Some(parent) = self.nodes[current].parent {
+            let idx = self.get_child_index(current);
+            self.nodes[parent].selected_child = idx;
+            current = parent;
+        }
+    }
+    fn get_child_index(&self, node: NodeId) -> usize {
+        let parent = self.nodes[node].parent.expect("The root has no parent.");
+        self.nodes[parent]
+            .children
+            .iter()
+            .position(|&child| child == node)
+            .unwrap()
+    }
+
+    /// Returns the nodes leading from the root to a given one, excluding the
+    /// root itself.
+    pub fn get_path(&self, node: NodeId) -> Vec<NodeId> {
+        let mut path = vec![];
+        let mut current = node;
+        while let Some(parent) = self.nodes[current].parent {
+            path.push(current);
+            current = parent;
+        }
+        path.reverse();
+        path
+    }
+    /// Returns the nodes of the line going through a given node, from the
+    /// first move until the end of the selected continuations.
+    pub fn get_line(&self, node: NodeId) -> Vec<NodeId> {
+        let mut line = self.get_path(node);
+        let mut current = node;
+        while let Some(child) = self.get_selected_child(current) {
+            line.push(child);
+            current = child;
+        }
+        line
+    }
+    /// Returns the last node of the main line.
+    pub fn get_main_line_end(&self) -> NodeId {
+        let mut current = ROOT;
+        while let Some(&child) = self.nodes[current].children.first() {
+            current = child;
+        }
+        current
+    }
+    /// Returns true if a node belongs to the main line of the game.
+    pub fn is_in_main_line(&self, node: NodeId) -> bool {
+        self.get_path(node)
+            .into_iter()
+            .all(|node| self.get_child_index(node) == 0)
+    }
+
+    /// Makes the line leading to a given node the main line; the lines it
+    /// has been a variation of become its variations.
+    pub fn promote_to_main_line(&mut self, node: NodeId) {
+        for current in self.get_path(node) {
+            let idx = self.get_child_index(current);
+            let parent = self.nodes[current].parent.unwrap();
+            let parent = &mut self.nodes[parent];
+            let child = parent.children.remove(idx);
+            parent.children.insert(0, child);
+            parent.selected_child = 0;
+        }
+    }
+    /// Removes a node along with all its continuations and returns its
+    /// parent; the root can't be removed.
+    pub fn delete(&mut self, node: NodeId) -> Option<NodeId> {
+        let parent = self.nodes[node].parent?;
+        let idx = self.get_child_index(node);
+        let parent_node = &mut self.nodes[parent];
+        parent_node.children.remove(idx);
+        if parent_node.selected_child >= idx && parent_node.selected_child > 0 {
+            parent_node.selected_child -= 1;
+        }
+        Some(parent)
+    }
+
+    /// Builds a tree by playing the moves of a PGN movetext, including its
+    /// variations, from a given state.
+    pub fn from_movetext(state: GameState, movetext: &[MovetextElement]) -> Result<Self, String> {
+        let mut tree = Self::new(state);
+        tree.read_line(ROOT, movetext, false)?;
+        tree.select(tree.get_main_line_end());
+        Ok(tree)
+    }
+    fn read_line(
+        &mut self,
+        start: NodeId,
+        movetext: &[MovetextElement],
+        is_variation: bool,
+    ) -> Result<(), String> {
+        let mut current = start;
+        let mut starting_comments = vec![];
+        for element in movetext {
+            match element {
+                MovetextElement::Move(san) => {
+                    let state = self.get_state(current);
+                    let move_number = state.get_fullmove_number();
+                    let ply_name = match state.get_current_player() {
+                        Player::White => format!("{}. {}", move_number, san),
+                        Player::Black => format!("{}... {}", move_number, san),
+                    };
+                    let ply = self.get_ply(current) + 1;
+                    let chess_move = if state.get_result().is_ongoing() {
+                        Move::from_san(state, san)
+                    } else {
+                        Err("The game has already finished".to_string())
+                    }
+                    .map_err(|error| format!("Ply {} ({}): {}", ply, ply_name, error))?;
+                    current = self.add_move(current, chess_move);
+                    self.nodes[current]
+                        .starting_comments
+                        .append(&mut starting_comments);
+                }
+                MovetextElement::Comment(comment) => {
+                    if is_variation && current == start {
+                        starting_comments.push(comment.clone());
+                    } else {
+                        self.add_comment(current, comment);
+                    }
+                }
+                MovetextElement::Nag(nag) => self.add_nag(current, *nag),
+                MovetextElement::Variation(variation) => match self.get_parent(current) {
+                    Some(parent) if current != start => self.read_line(parent, variation, true)?,
+                    _ => return Err("A variation has to follow a move".to_string()),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the movetext of the whole game, including comments, NAGs and
+    /// variations.
+    pub fn to_movetext(&self) -> Vec<MovetextElement> {
+        let root = &self.nodes[ROOT];
+        let mut movetext: Vec<MovetextElement> = root
+            .comments
+            .iter()
+            .cloned()
+            .map(MovetextElement::Comment)
+            .collect();
+        movetext.extend(root.nags.iter().map(|&nag| MovetextElement::Nag(nag)));
+        if let Some(&first) = root.children.first() {
+            self.write_line(first, &mut movetext);
+        }
+        movetext
+    }
+    /// Writes the line starting with a given node, following the main
+    /// continuations; the other ones are written as variations right after
+    /// the move they are an alternative to.
+    fn write_line(&self, first: NodeId, movetext: &mut Vec<MovetextElement>) {
+        let mut current = Some(first);
+        while let Some(node_id) = current {
+            let node = &self.nodes[node_id];
+            movetext.extend(
+                node.starting_comments
+                    .iter()
+                    .cloned()
+                    .map(MovetextElement::Comment),
+            );
+            movetext.push(MovetextElement::Move(node.san.clone()));
+            movetext.extend(node.nags.iter().map(|&nag| MovetextElement::Nag(nag)));
+            movetext.extend(node.comments.iter().cloned().map(MovetextElement::Comment));
+            let siblings = &self.nodes[node.parent.unwrap()].children;
+            if siblings[0] == node_id {
+                for &sibling in &siblings[1..] {
+                    let mut variation = vec![];
+                    self.write_line(sibling, &mut variation);
+                    movetext.push(MovetextElement::Variation(variation));
+                }
+            }
+            current = node.children.first().copied();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::PgnGames;
+
+    fn read_tree(text: &str) -> Result<GameTree, String> {
+        let game = PgnGames::new(text).next().unwrap().unwrap();
+        GameTree::from_movetext(GameState::init(), &game.movetext)
+    }
+
+    fn sans(tree: &GameTree, nodes: &[NodeId]) -> Vec<String> {
+        nodes
+            .iter()
+            .map(|&node| tree.get_san(node).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_movetext_round_trip() {
+        let text = "{Intro} 1. e4 e5 $1 {Solid} (1... c5 {Sicilian} 2. Nf3 (2. c3) d6) \
+                    ({Rare} 1... a6) 2. Nf3 *";
+        let game = PgnGames::new(text).next().unwrap().unwrap();
+        let tree = GameTree::from_movetext(GameState::init(), &game.movetext).unwrap();
+        let main_line = tree.get_line(ROOT);
+        assert_eq!(sans(&tree, &main_line), ["e4", "e5", "Nf3"]);
+        let alternatives = tree.get_children(main_line[0]).to_vec();
+        assert_eq!(sans(&tree, &alternatives), ["e5", "c5", "a6"]);
+        assert_eq!(tree.get_nags(main_line[1]), [1]);
+        assert_eq!(tree.get_comments(main_line[1]), ["Solid"]);
+        assert_eq!(tree.get_comments(ROOT), ["Intro"]);
+
+        let movetext = tree.to_movetext();
+        let reread = GameTree::from_movetext(GameState::init(), &movetext).unwrap();
+        assert_eq!(reread.to_movetext(), movetext);
+        assert_eq!(movetext.len(), 8);
+        assert_eq!(
+            movetext[6],
+            MovetextElement::Variation(vec![
+                MovetextElement::Comment("Rare".to_string()),
+                MovetextElement::Move("a6".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_movetext_errors() {
+        assert_eq!(
+            read_tree("1. e4 (1. d4 Nf6 2. Nf4) e5 *").err(),
+            Some("Ply 3 (2. Nf4): No legal move matches 'Nf4'".to_string())
+        );
+        assert_eq!(
+            read_tree("(1. d4) 1. e4 *").err(),
+            Some("A variation has to follow a move".to_string())
+        );
+    }
+
+    #[test]
+    fn test_tree_editing() {
+        let mut tree = read_tree("1. e4 e5 (1... c5 2. Nf3 (2. c3)) 2. Nf3 *").unwrap();
+        let c3 = tree.get_line(ROOT)[0];
+        let c3 = tree.get_children(c3)[1];
+        let c3 = tree.get_children(c3)[1];
+        assert_eq!(tree.get_san(c3), "c3");
+        assert!(!tree.is_in_main_line(c3));
+        tree.promote_to_main_line(c3);
+        assert!(tree.is_in_main_line(c3));
+        assert_eq!(sans(&tree, &tree.get_line(ROOT)), ["e4", "c5", "c3"]);
+        assert_eq!(tree.get_main_line_end(), c3);
+
+        let c5 = tree.delete(c3).unwrap();
+        assert_eq!(tree.get_san(c5), "c5");
+        assert_eq!(sans(&tree, &tree.get_line(ROOT)), ["e4", "c5", "Nf3"]);
+        let e4 = tree.delete(c5).unwrap();
+        assert_eq!(sans(&tree, tree.get_children(e4)), ["e5"]);
+        assert_eq!(tree.delete(ROOT), None);
+
+        let nf3 = tree.get_main_line_end();
+        let e5 = tree.get_parent(nf3).unwrap();
+        let d4 = Move::from_san(tree.get_state(e5), "d4").unwrap();
+        let d4 = tree.add_move(e5, d4);
+        assert_eq!(tree.get_selected_child(e5), Some(d4));
+        assert_eq!(sans(&tree, &tree.get_line(ROOT)), ["e4", "e5", "d4"]);
+        assert_eq!(tree.get_main_line_end(), nf3);
+        assert_eq!(tree.add_move(e5, tree.get_move(nf3).unwrap()), nf3);
+    }
+}
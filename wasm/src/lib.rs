@@ -1,23 +1,39 @@
+use clock::{Clock, TimeControl};
+pub use gameresult::{GameResult, GameStatus};
 use gamestate::GameState;
+use gametree::{GameTree, NodeId};
 use itertools::Itertools;
 pub use piece::Piece;
 pub use position::Position;
 pub use r#move::Move;
 use wasm_bindgen::prelude::*;
 
+use crate::difficulty::Difficulty;
 use crate::piece::{PieceData, PieceType};
-use crate::player::Player;
+use crate::player::{get_opponent, Player};
+use crate::search::{SearchLimit, SearchResult};
+use crate::transposition::TranspositionTable;
 
+mod bitboard;
+pub mod clock;
+pub mod difficulty;
+mod evaluation;
+pub mod gameresult;
 mod gamestate;
+mod gametree;
 pub mod r#move;
+pub mod pgn;
 pub mod piece;
 pub mod player;
 pub mod position;
+pub mod search;
+pub mod transposition;
 mod utils;
+mod zobrist;
 
 #[wasm_bindgen]
 #[repr(u8)]
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum PromotionType {
     Queen,
     Rook,
@@ -25,44 +41,274 @@ pub enum PromotionType {
     Knight,
 }
 
+impl PromotionType {
+    /// All the pieces a pawn can be promoted to, the most valuable first.
+    pub const ALL: [PromotionType; 4] = [Self::Queen, Self::Rook, Self::Bishop, Self::Knight];
+
+    /// Returns the type of the piece a pawn gets promoted to.
+    pub fn get_piece_type(&self) -> PieceType {
+        match self {
+            Self::Queen => PieceType::Queen,
+            Self::Rook => PieceType::Rook,
+            Self::Bishop => PieceType::Bishop,
+            Self::Knight => PieceType::Knight,
+        }
+    }
+    /// Returns the promotion to a piece of a given type, if a pawn can be
+    /// promoted to it.
+    pub fn from_piece_type(piece_type: PieceType) -> Option<Self> {
+        match piece_type {
+            PieceType::Queen => Some(Self::Queen),
+            PieceType::Rook => Some(Self::Rook),
+            PieceType::Bishop => Some(Self::Bishop),
+            PieceType::Knight => Some(Self::Knight),
+            _ => None,
+        }
+    }
+}
+
 #[wasm_bindgen]
 pub struct Game {
-    state: GameState,
+    /// The moves played in the game along with their variations.
+    tree: GameTree,
+    /// The node of the tree holding the current position.
+    current: NodeId,
+    /// A result declared by the players, e.g. a claimed draw, which takes
+    /// precedence over the one following from the state of the board. It
+    /// applies to the position at the end of the main line.
+    declared_result: Option<GameResult>,
+    /// The PGN tags set by the players, in the order they were first set.
+    tags: Vec<(String, String)>,
+    /// The clocks of the players, if the game is played with a time control.
+    clock: Option<Clock>,
+    /// The player offering a draw and the position in which the offer stands.
+    draw_offer: Option<(Player, NodeId)>,
+    /// The latest moment passed from JS, or at which a move was made, in
+    /// milliseconds.
+    clock_timestamp: u64,
+    /// The positions searched by the computer, kept between the searches of
+    /// `best_move`. It's only allocated once the computer is asked for a
+    /// move.
+    transposition_table: Option<TranspositionTable>,
+    /// The size of the transposition table in megabytes.
+    hash_size: u32,
+}
+
+/// A played move as seen from JS: written in SAN and UCI, together with the
+/// FEN of the position after it.
+#[wasm_bindgen(getter_with_clone)]
+pub struct HistoryEntry {
+    pub san: String,
+    pub uci: String,
+    pub fen: String,
 }
 
+/// The number of leaf nodes reached by one of the moves in a perft count,
+/// with the move written in UCI.
+#[wasm_bindgen(getter_with_clone)]
+pub struct PerftEntry {
+    pub uci: String,
+    pub nodes: u64,
+}
+
+/// A move chosen by the computer, written in SAN and UCI, with its score in
+/// centipawns from the perspective of the player making it. If the search
+/// has found a forced mate, `mate` holds the number of moves until it,
+/// negative if the player gets mated.
+#[wasm_bindgen(getter_with_clone)]
+pub struct EngineMove {
+    pub san: String,
+    pub uci: String,
+    pub score: i32,
+    pub mate: Option<i32>,
+    pub depth: u32,
+}
+
+/// The number of occurrences of a position after which a player can claim a
+/// draw.
+const THREEFOLD_REPETITION: usize = 3;
+/// The number of occurrences of a position after which the game is
+/// automatically drawn.
+const FIVEFOLD_REPETITION: usize = 5;
+
 #[wasm_bindgen]
 pub struct JsPos {
     pub row: u8,
     pub col: u8,
 }
 
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[wasm_bindgen]
 impl Game {
     pub fn new() -> Self {
-        utils::set_panic_hook();
-        Self {
-            state: GameState::init(),
+        Self::from_state(GameState::init())
+    }
+    /// Creates a game starting from a position written in the
+    /// Forsyth-Edwards Notation; returns Err(String) describing the problem if
+    /// the notation is malformed.
+    pub fn from_fen(fen: &str) -> Result<Game, String> {
+        GameState::from_fen(fen).map(Self::from_state)
+    }
+    /// Creates a game by replaying the main line of the first game stored in
+    /// a PGN file; returns Err(String) describing the problem if the file is
+    /// malformed or contains an illegal move.
+    pub fn from_pgn(pgn: &str) -> Result<Game, String> {
+        match pgn::PgnGames::new(pgn).next() {
+            Some(game) => Self::from_pgn_game(&game?),
+            None => Err("The PGN contains no games".to_string()),
         }
     }
+    /// Returns the current position written in the Forsyth-Edwards Notation.
+    pub fn fen(&self) -> String {
+        self.get_state().to_fen()
+    }
+    /// Returns the Zobrist hash of the current position, which is the same
+    /// as its key in Polyglot opening books.
+    pub fn get_hash(&self) -> u64 {
+        self.get_state().get_position_key()
+    }
     /// Returns a list of possible positions that a piece on a given square can
     /// get to within a move.
     pub fn get_moves(&self, row: u8, column: u8) -> Vec<JsPos> {
         let pos = Position::new(row, column).unwrap();
-        self.state
+        self.get_state()
             .get_moves(pos)
             .into_iter()
-            .map(move |x| {
-                let pos = x.get_end_position();
-                JsPos {
-                    row: pos.get_row(),
-                    col: pos.get_column(),
-                }
+            .map(|x| x.get_end_position())
+            .unique()
+            .map(|pos| JsPos {
+                row: pos.get_row(),
+                col: pos.get_column(),
             })
             .collect_vec()
     }
     /// Returns true if the game has finished.
     pub fn is_finished(&self) -> bool {
-        self.state.is_finished()
+        !self.get_result().is_ongoing()
+    }
+    /// Returns the status of the game, e.g. whether it has ended with a
+    /// checkmate or a stalemate. The winner, if there is one, can be obtained
+    /// with get_winner.
+    pub fn get_status(&self) -> GameStatus {
+        self.get_result().get_status()
+    }
+    /// Returns true if the current player is under check.
+    pub fn is_check(&self) -> bool {
+        self.get_state().is_checked()
+    }
+    /// Returns the number of halfmoves since the last capture or pawn move.
+    pub fn get_halfmove_clock(&self) -> u32 {
+        self.get_state().get_halfmove_clock()
+    }
+    /// Returns the number of the current full move.
+    pub fn get_fullmove_number(&self) -> u32 {
+        self.get_state().get_fullmove_number()
+    }
+    /// Returns true if the current player can claim a draw.
+    pub fn can_claim_draw(&self) -> bool {
+        self.get_claimable_draw().is_some()
+    }
+    /// Tries to claim a draw; returns Ok(()) if the game has ended in a draw,
+    /// Err(String) if there is no draw to claim. A draw can only be claimed
+    /// in the last position of an unfinished game.
+    pub fn claim_draw(&mut self) -> Result<(), String> {
+        match self.get_claimable_draw() {
+            Some(result) => {
+                self.declare_result(result);
+                Ok(())
+            }
+            None => Err("There is no draw to claim".to_string()),
+        }
+    }
+    /// Ends the game with a given player resigning; it has to be in its last
+    /// position, not one gone back to.
+    pub fn resign(&mut self, player: Player) -> Result<(), String> {
+        self.check_can_end()?;
+        self.declare_result(GameResult::Resignation(get_opponent(player)));
+        Ok(())
+    }
+    /// Offers a draw on behalf of a given player; the offer stands until the
+    /// opponent accepts or declines it, or makes a move. An offer made when
+    /// the opponent has already offered a draw accepts it.
+    pub fn offer_draw(&mut self, player: Player) -> Result<(), String> {
+        self.check_can_end()?;
+        match self.get_draw_offer() {
+            Some(offering_player) if offering_player != player => self.accept_draw(player),
+            _ => {
+                self.draw_offer = Some((player, self.current));
+                Ok(())
+            }
+        }
+    }
+    /// Accepts the draw offered by the opponent of a given player.
+    pub fn accept_draw(&mut self, player: Player) -> Result<(), String> {
+        if self.get_draw_offer() != Some(get_opponent(player)) {
+            return Err("There is no draw offer to accept".to_string());
+        }
+        self.check_can_end()?;
+        self.declare_result(GameResult::DrawByAgreement);
+        Ok(())
+    }
+    /// Declines the draw offered by the opponent of a given player.
+    pub fn decline_draw(&mut self, player: Player) -> Result<(), String> {
+        if self.get_draw_offer() != Some(get_opponent(player)) {
+            return Err("There is no draw offer to decline".to_string());
+        }
+        self.draw_offer = None;
+        Ok(())
+    }
+    /// Returns the player whose draw offer is pending, if any.
+    pub fn get_draw_offer(&self) -> Option<Player> {
+        self.draw_offer
+            .filter(|&(_, node)| node == self.current && !self.is_finished())
+            .map(|(player, _)| player)
+    }
+    /// Sets the time control the game is played with; all the times are given
+    /// in milliseconds. It can't be changed once the clock has been started.
+    pub fn set_time_control(&mut self, time_control: TimeControl) -> Result<(), String> {
+        if self
+            .clock
+            .as_ref()
+            .is_some_and(|clock| clock.get_running_player().is_some())
+        {
+            return Err("The clock has already been started".to_string());
+        }
+        self.clock = Some(Clock::new(time_control)?);
+        Ok(())
+    }
+    /// Starts the clock of the player to move at a given moment, given in
+    /// milliseconds, e.g. as returned by Date.now().
+    pub fn start_clock(&mut self, timestamp: f64) -> Result<(), String> {
+        if self.is_finished() {
+            return Err("The game has already finished".to_string());
+        }
+        let player = self.get_state().get_current_player();
+        let clock = self
+            .clock
+            .as_mut()
+            .ok_or_else(|| "The game has no time control".to_string())?;
+        self.clock_timestamp = timestamp as u64;
+        clock.start(player, self.clock_timestamp);
+        Ok(())
+    }
+    /// Advances the time of the game to a given moment, given in
+    /// milliseconds; the game ends if the time of the player to move has run
+    /// out. It should be called periodically to detect the flag fall.
+    pub fn update_clock(&mut self, timestamp: f64) {
+        self.clock_timestamp = self.clock_timestamp.max(timestamp as u64);
+        self.check_flag_fall();
+    }
+    /// Returns the time left for a given player in milliseconds, or None if
+    /// the game is played without a time control.
+    pub fn get_remaining_time(&self, player: Player) -> Option<f64> {
+        self.clock
+            .as_ref()
+            .map(|clock| clock.get_remaining(player, self.clock_timestamp) as f64)
     }
     /// Returns true if the move would result in a promotion of a pawn.
     pub fn is_promotion_move(
@@ -74,10 +320,18 @@ impl Game {
     ) -> bool {
         let from = Position::new(from_row, from_column).unwrap();
         let to = Position::new(to_row, to_column).unwrap();
-        self.state.is_promotion_move(Move::new(from, to))
+        self.get_state().is_promotion_move(Move::new(from, to))
     }
     /// Tries to make a move; returns Ok(()) if the move was successful,
-    /// Err(String) otherwise.
+    /// Err(String) otherwise. A move promoting a pawn has to name the piece.
+    /// A move made on the main line of an unfinished game replaces the moves
+    /// taken back with undo, while elsewhere, e.g. in a variation or in a
+    /// finished game, it starts a new variation; see also add_variation.
+    ///
+    /// In a game with a clock the move is timed at a given moment in
+    /// milliseconds, which has to come from the same source as the ones passed
+    /// to start_clock, e.g. Date.now(). If it's left out, the move is timed
+    /// at the current time.
     pub fn make_move(
         &mut self,
         from_row: u8,
@@ -85,35 +339,621 @@ impl Game {
         to_row: u8,
         to_column: u8,
         promotion_type: Option<PromotionType>,
+        timestamp: Option<f64>,
     ) -> Result<(), String> {
         let from = Position::new(from_row, from_column).unwrap();
         let to = Position::new(to_row, to_column).unwrap();
-        let chess_move = Move::new(from, to);
-        let possible_moves = self
-            .state
-            .get_moves(chess_move.get_current_position())
+        self.play_move(
+            Move::new(from, to).with_promotion(promotion_type),
+            timestamp,
+        )
+    }
+
+    /// Tries to make a move written in the Standard Algebraic Notation, e.g.
+    /// "Nf3", "exd5" or "e8=Q"; returns Ok(()) if the move was successful,
+    /// Err(String) otherwise. The move is timed as in make_move.
+    pub fn make_move_san(&mut self, san: &str, timestamp: Option<f64>) -> Result<(), String> {
+        if self.is_finished() {
+            return Err("The game has already finished".to_string());
+        }
+        let chess_move = Move::from_san(self.get_state(), san)?;
+        self.play_move(chess_move, timestamp)
+    }
+
+    /// Tries to make a move written in the UCI long algebraic notation, e.g.
+    /// "e2e4" or "e7e8q"; returns Ok(()) if the move was successful,
+    /// Err(String) otherwise. The move is timed as in make_move.
+    pub fn make_move_uci(&mut self, uci: &str, timestamp: Option<f64>) -> Result<(), String> {
+        self.play_move(Move::from_uci(uci)?, timestamp)
+    }
+
+    /// Returns the moves leading to the current position written in SAN.
+    pub fn get_san_moves(&self) -> Vec<String> {
+        self.tree
+            .get_path(self.current)
             .into_iter()
-            .map(move |x| x.get_end_position())
-            .collect_vec();
-        if possible_moves.contains(&chess_move.get_end_position()) {
-            self.state = GameState::transform_state(&self.state, chess_move, promotion_type);
-            Ok(())
-        } else {
-            Err("Invalid move".to_string())
+            .map(|node| self.tree.get_san(node).to_string())
+            .collect()
+    }
+
+    /// Returns the moves of the line going through the current position,
+    /// including the ones which have been undone and can still be redone;
+    /// get_ply tells how many of them lead to the current position.
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        self.tree
+            .get_line(self.current)
+            .into_iter()
+            .map(|node| HistoryEntry {
+                san: self.tree.get_san(node).to_string(),
+                uci: self.tree.get_move(node).unwrap().to_uci(),
+                fen: self.tree.get_state(node).to_fen(),
+            })
+            .collect()
+    }
+
+    /// Returns the number of moves leading to the current position.
+    pub fn get_ply(&self) -> usize {
+        self.tree.get_ply(self.current)
+    }
+
+    /// Takes back the last move; it can be played again with redo.
+    pub fn undo(&mut self) -> Result<(), String> {
+        match self.tree.get_parent(self.current) {
+            Some(parent) => {
+                self.current = parent;
+                Ok(())
+            }
+            None => Err("There is no move to undo".to_string()),
         }
     }
 
+    /// Plays again the last move taken back with undo, or the next move of
+    /// the line.
+    pub fn redo(&mut self) -> Result<(), String> {
+        match self.tree.get_selected_child(self.current) {
+            Some(child) => {
+                self.current = child;
+                Ok(())
+            }
+            None => Err("There is no move to redo".to_string()),
+        }
+    }
+
+    /// Moves to the position after a given number of moves of the current
+    /// line, e.g. 0 for the initial position; the moves after it can still be
+    /// redone.
+    pub fn goto_ply(&mut self, ply: usize) -> Result<(), String> {
+        let line = self.tree.get_line(self.current);
+        self.current = match ply {
+            0 => gametree::ROOT,
+            _ => *line
+                .get(ply - 1)
+                .ok_or_else(|| format!("The line has only {} moves", line.len()))?,
+        };
+        Ok(())
+    }
+
+    /// Returns the continuations of the current position written in SAN; the
+    /// first one belongs to the main line and the others are its variations.
+    pub fn get_variations(&self) -> Vec<String> {
+        self.tree
+            .get_children(self.current)
+            .iter()
+            .map(|&node| self.tree.get_san(node).to_string())
+            .collect()
+    }
+
+    /// Makes a move written in SAN as a new variation of the current
+    /// position, keeping its other continuations, even the ones taken back on
+    /// the main line.
+    pub fn add_variation(&mut self, san: &str) -> Result<(), String> {
+        if self.is_finished() {
+            return Err("The game has already finished".to_string());
+        }
+        let chess_move = Move::from_san(self.get_state(), san)?;
+        self.add_move(chess_move, true, None)
+    }
+
+    /// Plays a given continuation of the current position, as returned by
+    /// get_variations, making it part of the current line.
+    pub fn enter_variation(&mut self, index: usize) -> Result<(), String> {
+        let child = *self
+            .tree
+            .get_children(self.current)
+            .get(index)
+            .ok_or_else(|| format!("There is no variation number {}", index))?;
+        self.tree.select(child);
+        self.current = child;
+        Ok(())
+    }
+
+    /// Returns true if the current position belongs to the main line.
+    pub fn is_in_main_line(&self) -> bool {
+        self.tree.is_in_main_line(self.current)
+    }
+
+    /// Makes the line leading to the current position the main line of the
+    /// game. A result declared for the previous main line no longer applies.
+    pub fn promote_variation(&mut self) {
+        if !self.tree.is_in_main_line(self.current) {
+            self.tree.promote_to_main_line(self.current);
+            self.declared_result = None;
+        }
+    }
+
+    /// Deletes the last move along with all its continuations and goes back
+    /// to the position before it.
+    pub fn delete_branch(&mut self) -> Result<(), String> {
+        let is_in_main_line = self.tree.is_in_main_line(self.current);
+        let parent = self
+            .tree
+            .delete(self.current)
+            .ok_or_else(|| "There is no move to delete".to_string())?;
+        if is_in_main_line {
+            self.declared_result = None;
+        }
+        self.current = parent;
+        Ok(())
+    }
+
+    /// Adds a comment to the last move, or to the game itself in the initial
+    /// position.
+    pub fn add_comment(&mut self, comment: &str) {
+        self.tree.add_comment(self.current, comment);
+    }
+
+    /// Returns the comments of the last move.
+    pub fn get_comments(&self) -> Vec<String> {
+        self.tree.get_comments(self.current).to_vec()
+    }
+
+    /// Removes the comments of the last move.
+    pub fn clear_comments(&mut self) {
+        self.tree.clear_comments(self.current);
+    }
+
+    /// Annotates the last move with a Numeric Annotation Glyph, e.g. 1 for a
+    /// good move ("!") or 4 for a blunder ("??").
+    pub fn add_nag(&mut self, nag: u8) {
+        self.tree.add_nag(self.current, nag);
+    }
+
+    /// Removes a Numeric Annotation Glyph from the last move.
+    pub fn remove_nag(&mut self, nag: u8) {
+        self.tree.remove_nag(self.current, nag);
+    }
+
+    /// Returns the Numeric Annotation Glyphs of the last move.
+    pub fn get_nags(&self) -> Vec<u8> {
+        self.tree.get_nags(self.current).to_vec()
+    }
+
+    /// Returns the player who is to move.
+    pub fn get_current_player(&self) -> Player {
+        self.get_state().get_current_player()
+    }
+
+    /// Returns all the legal moves of the current player written in SAN;
+    /// every possible promotion is listed separately.
+    pub fn get_legal_moves_san(&self) -> Vec<String> {
+        if self.is_finished() {
+            return vec![];
+        }
+        self.get_state()
+            .get_all_moves()
+            .iter()
+            .map(|chess_move| chess_move.to_san(self.get_state()))
+            .collect()
+    }
+
+    /// Returns the number of the positions reached after a given number of
+    /// halfmoves from the current one (a perft count).
+    pub fn perft(&self, depth: u32) -> u64 {
+        self.get_state().perft(depth)
+    }
+
+    /// Returns the perft count of a given depth split between the legal
+    /// moves in the current position.
+    pub fn perft_divide(&self, depth: u32) -> Vec<PerftEntry> {
+        self.get_state()
+            .perft_divide(depth)
+            .into_iter()
+            .map(|(chess_move, nodes)| PerftEntry {
+                uci: chess_move.to_uci(),
+                nodes,
+            })
+            .collect()
+    }
+
+    /// Returns the static evaluation of the current position in centipawns
+    /// from White's perspective: positive if White stands better.
+    pub fn evaluate(&self) -> i32 {
+        evaluation::evaluate(self.get_state(), &evaluation::EvalWeights::default())
+    }
+
+    /// Searches for the best move of the current player within a given limit,
+    /// so it can be played by the computer.
+    pub fn best_move(&mut self, limit: SearchLimit) -> Result<EngineMove, String> {
+        if self.is_finished() {
+            return Err("The game is finished".to_string());
+        }
+        let state = self.get_state().clone();
+        let history_keys = self.get_history_keys();
+        let result = search::find_best_move(&state, limit, history_keys, self.get_table()?);
+        self.to_engine_move(result)
+    }
+
+    /// Sets the size of the transposition table used by `best_move` and
+    /// `computer_move` in megabytes, clearing it.
+    pub fn set_hash_size(&mut self, megabytes: u32) -> Result<(), String> {
+        transposition::check_size(megabytes)?;
+        self.hash_size = megabytes;
+        if self.transposition_table.is_some() {
+            // The old table is freed before the new one is allocated.
+            self.transposition_table = None;
+            self.transposition_table = Some(TranspositionTable::new(megabytes)?);
+        }
+        Ok(())
+    }
+
+    /// Forgets all the positions searched by `best_move`.
+    pub fn clear_hash(&mut self) {
+        if let Some(table) = &mut self.transposition_table {
+            table.clear();
+        }
+    }
+
+    /// Chooses the move of the computer playing at a given difficulty. The
+    /// weaker levels choose randomly; the same seed always gives the same
+    /// move in the same position. To keep it so, the transposition table
+    /// shared with `best_move` is cleared before choosing the move.
+    pub fn computer_move(
+        &mut self,
+        difficulty: Difficulty,
+        seed: u64,
+    ) -> Result<EngineMove, String> {
+        if self.is_finished() {
+            return Err("The game is finished".to_string());
+        }
+        let state = self.get_state().clone();
+        let history_keys = self.get_history_keys();
+        let result =
+            difficulty::choose_move(&state, difficulty, seed, history_keys, self.get_table()?);
+        self.to_engine_move(result)
+    }
+
+    /// Sets the value of a PGN tag, e.g. "White" or "Event", to be included
+    /// in the exported game. The "Result" and "Termination" tags are always
+    /// derived from the game itself and can't be set.
+    pub fn set_tag(&mut self, name: &str, value: &str) -> Result<(), String> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("Invalid tag name '{}'", name));
+        }
+        if ["Result", "Termination", "SetUp", "FEN"].contains(&name) {
+            return Err(format!("The '{}' tag is set automatically", name));
+        }
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old_value)) => *old_value = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Returns the game written in the Portable Game Notation, including all
+    /// its variations and annotations. The result is the one at the end of the
+    /// main line.
+    pub fn pgn(&self) -> String {
+        let result = self.get_result_at(self.tree.get_main_line_end());
+        let result_token = pgn::get_result_token(result);
+        let mut tags: Vec<(String, String)> = pgn::SEVEN_TAG_ROSTER
+            .iter()
+            .map(|&name| {
+                let value = match name {
+                    "Result" => result_token.to_string(),
+                    _ => self
+                        .get_tag(name)
+                        .unwrap_or(pgn::get_default_tag_value(name))
+                        .to_string(),
+                };
+                (name.to_string(), value)
+            })
+            .collect();
+        let initial_state = self.tree.get_state(gametree::ROOT);
+        let initial_fen = initial_state.to_fen();
+        if initial_fen != GameState::init().to_fen() {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), initial_fen));
+        }
+        if !result.is_ongoing() {
+            tags.push((
+                "Termination".to_string(),
+                pgn::get_termination(result).to_string(),
+            ));
+        }
+        tags.extend(
+            self.tags
+                .iter()
+                .filter(|(name, _)| !pgn::SEVEN_TAG_ROSTER.contains(&name.as_str()))
+                .cloned(),
+        );
+        pgn::export_pgn(
+            &tags,
+            initial_state.get_fullmove_number(),
+            initial_state.get_current_player(),
+            &self.tree.to_movetext(),
+            result_token,
+        )
+    }
+
     /// Returns Some(Player) if a game has resulted in a win for a given player,
     /// None otherwise.
     pub fn get_winner(&self) -> Option<Player> {
-        self.state.get_winner()
+        self.get_result().get_winner()
     }
 
     /// Returns a PieceData struct containing info about the player and the
     /// type of the piece used.
     pub fn get_piece_data(&self, row: u8, column: u8) -> Option<PieceData> {
-        self.state
+        self.get_state()
             .get_piece(Position::new(row, column).unwrap())
             .map(|piece| piece.get_data())
     }
 }
+
+impl Game {
+    fn from_state(state: GameState) -> Self {
+        utils::set_panic_hook();
+        Self {
+            tree: GameTree::new(state),
+            current: gametree::ROOT,
+            declared_result: None,
+            tags: vec![],
+            draw_offer: None,
+            clock: None,
+            clock_timestamp: 0,
+            transposition_table: None,
+            hash_size: transposition::DEFAULT_SIZE,
+        }
+    }
+    /// Creates a game by replaying a game read from a PGN file along with its
+    /// variations; the game is left at the end of the main line. A decisive
+    /// result or a draw which doesn't follow from the final position is taken
+    /// to be a resignation or a draw by agreement.
+    pub fn from_pgn_game(pgn_game: &pgn::PgnGame) -> Result<Game, String> {
+        let mut game = match pgn_game.get_tag("FEN") {
+            Some(fen) => Self::from_fen(fen)?,
+            None => Self::new(),
+        };
+        for (name, value) in &pgn_game.tags {
+            if !["Result", "Termination", "SetUp", "FEN"].contains(&name.as_str()) {
+                game.set_tag(name, value)?;
+            }
+        }
+        game.tree = GameTree::from_movetext(game.get_state().clone(), &pgn_game.movetext)?;
+        game.current = game.tree.get_main_line_end();
+        if game.get_result().is_ongoing() {
+            let time_forfeit = pgn_game.get_tag("Termination") == Some("time forfeit");
+            game.declared_result = match (pgn_game.result.as_str(), time_forfeit) {
+                ("1-0", false) => Some(GameResult::Resignation(Player::White)),
+                ("0-1", false) => Some(GameResult::Resignation(Player::Black)),
+                ("1/2-1/2", false) => Some(GameResult::DrawByAgreement),
+                ("1-0", true) => Some(GameResult::Timeout(Player::White)),
+                ("0-1", true) => Some(GameResult::Timeout(Player::Black)),
+                ("1/2-1/2", true) => Some(GameResult::DrawByTimeout),
+                _ => None,
+            };
+        }
+        Ok(game)
+    }
+    /// Makes a move if it's legal in the current position. A move promoting a
+    /// pawn has to carry the piece, while any other move can't carry one. The
+    /// move is timed at a given moment, or at the current time if there is
+    /// none.
+    pub fn play_move(&mut self, chess_move: Move, timestamp: Option<f64>) -> Result<(), String> {
+        self.add_move(chess_move, false, timestamp)
+    }
+    /// Makes a move if it's legal in the current position, either as a new
+    /// variation or as the continuation of the line; see make_move.
+    fn add_move(
+        &mut self,
+        chess_move: Move,
+        as_variation: bool,
+        timestamp: Option<f64>,
+    ) -> Result<(), String> {
+        if self.clock.is_some() {
+            let timestamp = timestamp.unwrap_or_else(utils::now) as u64;
+            self.clock_timestamp = self.clock_timestamp.max(timestamp);
+        }
+        self.check_flag_fall();
+        if self.is_finished() {
+            return Err("The game has already finished".to_string());
+        }
+        let candidates = self
+            .get_state()
+            .get_moves(chess_move.get_current_position())
+            .into_iter()
+            .filter(|legal_move| legal_move.get_end_position() == chess_move.get_end_position())
+            .collect_vec();
+        let is_promotion = candidates
+            .iter()
+            .any(|legal_move| legal_move.get_promotion().is_some());
+        match (is_promotion, chess_move.get_promotion()) {
+            (true, None) => return Err("A promotion piece has to be chosen".to_string()),
+            (false, Some(_)) if !candidates.is_empty() => {
+                return Err("Only a pawn reaching the last rank can be promoted".to_string())
+            }
+            _ => (),
+        }
+        // The legal move carries the flags describing its kind.
+        let chess_move = candidates
+            .into_iter()
+            .find(|legal_move| *legal_move == chess_move)
+            .ok_or_else(|| "Invalid move".to_string())?;
+        let player = self.get_state().get_current_player();
+        let parent = self.current;
+        // A move played on the main line of an unfinished game, e.g. after
+        // taking a misclick back, replaces the moves taken back. Moves played
+        // in a variation stay there, as do the ones analysing a finished game.
+        let replaces_main_line = !as_variation
+            && self.tree.is_in_main_line(parent)
+            && self
+                .get_result_at(self.tree.get_main_line_end())
+                .is_ongoing();
+        if replaces_main_line {
+            if let Some(&main_child) = self.tree.get_children(parent).first() {
+                if self.tree.get_move(main_child) != Some(chess_move) {
+                    self.tree.delete(main_child);
+                }
+            }
+        }
+        self.current = self.tree.add_move(parent, chess_move);
+        if replaces_main_line {
+            self.tree.promote_to_main_line(self.current);
+        }
+        // A draw offer made by the player before their move stands, while any
+        // other one expires.
+        self.draw_offer = self
+            .draw_offer
+            .filter(|&offer| offer == (player, parent))
+            .map(|(player, _)| (player, self.current));
+        let is_finished = self.is_finished();
+        if let Some(clock) = &mut self.clock {
+            match is_finished {
+                true => clock.stop(self.clock_timestamp),
+                false => clock.press(self.clock_timestamp),
+            }
+        }
+        Ok(())
+    }
+    /// Returns Err(String) unless the game can be ended in the current
+    /// position, i.e. it's the last one of the main line and the game hasn't
+    /// finished yet.
+    fn check_can_end(&self) -> Result<(), String> {
+        let main_line_end = self.tree.get_main_line_end();
+        if !self.get_result_at(main_line_end).is_ongoing() {
+            return Err("The game has already finished".to_string());
+        }
+        if self.current != main_line_end {
+            return Err("The game can only be ended in its last position".to_string());
+        }
+        Ok(())
+    }
+    /// Ends the game at the end of the main line with a given result.
+    fn declare_result(&mut self, result: GameResult) {
+        self.declared_result = Some(result);
+        if let Some(clock) = &mut self.clock {
+            clock.stop(self.clock_timestamp);
+        }
+    }
+    /// Ends the game if the time of the player to move in the last position
+    /// has run out, whichever position is being looked at. The opponent wins,
+    /// unless they can't possibly checkmate.
+    fn check_flag_fall(&mut self) {
+        let Some(clock) = &self.clock else {
+            return;
+        };
+        let Some(player) = clock
+            .get_running_player()
+            .filter(|&player| clock.is_flagged(player, self.clock_timestamp))
+        else {
+            return;
+        };
+        let main_line_end = self.tree.get_main_line_end();
+        if !self.get_result_at(main_line_end).is_ongoing() {
+            return;
+        }
+        let opponent = get_opponent(player);
+        let state = self.tree.get_state(main_line_end);
+        let result = if state.has_mating_material(opponent) {
+            GameResult::Timeout(opponent)
+        } else {
+            GameResult::DrawByTimeout
+        };
+        self.declare_result(result);
+    }
+    /// Returns the keys of the positions preceding the current one, so a
+    /// search can detect repetitions.
+    fn get_history_keys(&self) -> Vec<gamestate::PositionKey> {
+        let mut history_keys = [gametree::ROOT]
+            .into_iter()
+            .chain(self.tree.get_path(self.current))
+            .map(|node| self.tree.get_position_key(node))
+            .collect_vec();
+        history_keys.pop();
+        history_keys
+    }
+    /// Returns the transposition table used by the searches, allocating it
+    /// with the set size on first use; returns Err(String) if it can't be
+    /// allocated.
+    fn get_table(&mut self) -> Result<&mut TranspositionTable, String> {
+        if self.transposition_table.is_none() {
+            self.transposition_table = Some(TranspositionTable::new(self.hash_size)?);
+        }
+        Ok(self.transposition_table.as_mut().unwrap())
+    }
+    /// Describes the move found by a search in the current position for JS.
+    fn to_engine_move(&self, result: SearchResult) -> Result<EngineMove, String> {
+        let chess_move = result
+            .best_move
+            .ok_or_else(|| "There are no legal moves".to_string())?;
+        Ok(EngineMove {
+            san: chess_move.to_san(self.get_state()),
+            uci: chess_move.to_uci(),
+            score: result.score,
+            mate: result.get_mate(),
+            depth: result.depth,
+        })
+    }
+    /// Returns the state of the current position.
+    fn get_state(&self) -> &GameState {
+        self.tree.get_state(self.current)
+    }
+    /// Returns the value of a PGN tag set by the players.
+    fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+    /// Returns the result of the game in the current position.
+    pub fn get_result(&self) -> GameResult {
+        self.get_result_at(self.current)
+    }
+    /// Returns the result of the game in the position of a given node.
+    fn get_result_at(&self, node: NodeId) -> GameResult {
+        if let Some(result) = self.declared_result {
+            if node == self.tree.get_main_line_end() {
+                return result;
+            }
+        }
+        match self.tree.get_state(node).get_result() {
+            GameResult::Ongoing if self.get_repetition_count(node) >= FIVEFOLD_REPETITION => {
+                GameResult::DrawByRepetition
+            }
+            result => result,
+        }
+    }
+    /// Returns how many times the position of a given node has occurred in
+    /// the line leading to it.
+    fn get_repetition_count(&self, node: NodeId) -> usize {
+        let key = self.tree.get_position_key(node);
+        self.tree
+            .get_path(node)
+            .into_iter()
+            .chain([gametree::ROOT])
+            .filter(|&other| self.tree.get_position_key(other) == key)
+            .count()
+    }
+    /// Returns the kind of draw the current player can claim, if any.
+    fn get_claimable_draw(&self) -> Option<GameResult> {
+        if self.check_can_end().is_err() {
+            None
+        } else if self.get_state().can_claim_fifty_move_draw() {
+            Some(GameResult::DrawByFiftyMoves)
+        } else if self.get_repetition_count(self.current) >= THREEFOLD_REPETITION {
+            Some(GameResult::DrawByRepetition)
+        } else {
+            None
+        }
+    }
+}
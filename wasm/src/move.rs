@@ -1,18 +1,48 @@
-use crate::Position;
+use crate::gameresult::GameResult;
+use crate::gamestate::GameState;
+use crate::piece::PieceType;
+use crate::{Position, PromotionType};
+use std::cmp::Ordering;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// A move of a piece from one square to another, together with the piece a
+/// pawn gets promoted to. Moves generated for a given state also carry flags
+/// describing their kind; since those follow from the state, they are not
+/// taken into account when moves are compared.
+#[derive(Debug, Clone, Copy)]
 pub struct Move {
     current_position: Position,
     end_position: Position,
+    promotion: Option<PromotionType>,
+    flags: u8,
 }
 
 impl Move {
+    /// The move captures a piece.
+    pub const CAPTURE: u8 = 1;
+    /// The move captures a pawn en passant; such a move is also a capture.
+    pub const EN_PASSANT: u8 = 1 << 1;
+    /// The move is a castling, denoted by the move of the king.
+    pub const CASTLE: u8 = 1 << 2;
+    /// The move is a pawn advancing two squares.
+    pub const DOUBLE_PUSH: u8 = 1 << 3;
+
+    /// Creates a move without a promotion or any flags.
     pub fn new(from: Position, to: Position) -> Move {
+        Self::with_flags(from, to, 0)
+    }
+    /// Creates a move of a given kind, described by a combination of flags.
+    pub fn with_flags(from: Position, to: Position, flags: u8) -> Move {
         Self {
             current_position: from,
             end_position: to,
+            promotion: None,
+            flags,
         }
     }
+    /// Returns the same move promoting a pawn to a given piece.
+    pub fn with_promotion(self, promotion: Option<PromotionType>) -> Move {
+        Self { promotion, ..self }
+    }
     pub fn get_end_position(&self) -> Position {
         self.end_position
     }
@@ -20,4 +50,427 @@ impl Move {
     pub fn get_current_position(&self) -> Position {
         self.current_position
     }
+
+    /// Returns the piece a pawn gets promoted to, if the move is a promotion.
+    pub fn get_promotion(&self) -> Option<PromotionType> {
+        self.promotion
+    }
+    pub fn is_capture(&self) -> bool {
+        self.flags & Self::CAPTURE != 0
+    }
+    pub fn is_en_passant(&self) -> bool {
+        self.flags & Self::EN_PASSANT != 0
+    }
+    pub fn is_castle(&self) -> bool {
+        self.flags & Self::CASTLE != 0
+    }
+    pub fn is_double_push(&self) -> bool {
+        self.flags & Self::DOUBLE_PUSH != 0
+    }
+
+    fn get_key(&self) -> (Position, Position, Option<PromotionType>) {
+        (self.current_position, self.end_position, self.promotion)
+    }
+
+    /// Returns the move written in the Standard Algebraic Notation, given the
+    /// state in which it is made. The move is assumed to be a legal one
+    /// generated for the state.
+    pub fn to_san(&self, state: &GameState) -> String {
+        let piece = state
+            .get_piece(self.current_position)
+            .expect("There is no piece to move.");
+        let (start_row, start_col) = self.current_position.as_tuple();
+        let end_col = self.end_position.get_column();
+        let mut result = String::new();
+        if self.is_castle() {
+            result.push_str(if end_col > start_col { "O-O" } else { "O-O-O" });
+        } else {
+            if piece.get_type() == PieceType::Pawn {
+                if self.is_capture() {
+                    result.push((b'a' + start_col) as char);
+                }
+            } else {
+                result.push(piece.get_type().to_char());
+                // Other pieces of the same kind that could move to the same
+                // square have to be told apart by the file, the rank or both.
+                let rivals: Vec<Position> = state
+                    .get_pieces()
+                    .filter(|other| {
+                        other.get_player() == piece.get_player()
+                            && other.get_type() == piece.get_type()
+                            && other.get_position() != self.current_position
+                    })
+                    .map(|other| other.get_position())
+                    .filter(|&position| {
+                        state
+                            .get_moves(position)
+                            .iter()
+                            .any(|r#move| r#move.end_position == self.end_position)
+                    })
+                    .collect();
+                if !rivals.is_empty() {
+                    let square = self.current_position.to_string();
+                    if rivals.iter().all(|pos| pos.get_column() != start_col) {
+                        result.push_str(&square[..1]);
+                    } else if rivals.iter().all(|pos| pos.get_row() != start_row) {
+                        result.push_str(&square[1..]);
+                    } else {
+                        result.push_str(&square);
+                    }
+                }
+            }
+            if self.is_capture() {
+                result.push('x');
+            }
+            result.push_str(&self.end_position.to_string());
+            if let Some(promotion_type) = self.promotion {
+                result.push('=');
+                result.push(promotion_type.get_piece_type().to_char());
+            }
+        }
+        let new_state = GameState::transform_state(state, *self);
+        if let GameResult::Checkmate(_) = new_state.get_result() {
+            result.push('#');
+        } else if new_state.is_checked() {
+            result.push('+');
+        }
+        result
+    }
+
+    /// Finds the legal move written in the Standard Algebraic Notation in a
+    /// given state. Check and mate indicators as well as annotation marks are
+    /// ignored; castling may be written with either letters or zeros.
+    pub fn from_san(state: &GameState, san: &str) -> Result<Move, String> {
+        let text = san.trim_end_matches(['+', '#', '!', '?']);
+        let player = state.get_current_player();
+        if let Some(kingside) = match text {
+            "O-O" | "0-0" => Some(true),
+            "O-O-O" | "0-0-0" => Some(false),
+            _ => None,
+        } {
+            let king = state
+                .get_pieces()
+                .find(|piece| piece.get_player() == player && piece.get_type() == PieceType::King)
+                .ok_or_else(|| format!("There is no king to castle with in '{}'", san))?;
+            return state
+                .get_moves(king.get_position())
+                .into_iter()
+                .find(|r#move| {
+                    r#move.is_castle() && (r#move.end_position.get_column() == 6) == kingside
+                })
+                .ok_or_else(|| format!("Castling is not possible in '{}'", san));
+        }
+
+        let invalid = || format!("Invalid SAN move '{}'", san);
+        let (body, promotion) = match text.split_once('=') {
+            Some((body, piece)) => {
+                let mut chars = piece.chars();
+                let promotion = match (chars.next(), chars.next()) {
+                    (Some(symbol), None) => PieceType::from_char(symbol)
+                        .filter(|_| symbol.is_ascii_uppercase())
+                        .and_then(PromotionType::from_piece_type)
+                        .ok_or_else(invalid)?,
+                    _ => return Err(invalid()),
+                };
+                (body, Some(promotion))
+            }
+            None => (text, None),
+        };
+        let (piece_type, rest) = match body.chars().next() {
+            Some(symbol) if symbol.is_ascii_uppercase() => (
+                PieceType::from_char(symbol)
+                    .filter(|&piece_type| piece_type != PieceType::Pawn)
+                    .ok_or_else(invalid)?,
+                &body[1..],
+            ),
+            _ => (PieceType::Pawn, body),
+        };
+        if rest.len() < 2 || !rest.is_ascii() {
+            return Err(invalid());
+        }
+        let (qualifier, square) = rest.split_at(rest.len() - 2);
+        let end_position: Position = square.parse().map_err(|_| invalid())?;
+        let qualifier = qualifier.strip_suffix('x').unwrap_or(qualifier);
+        let (mut file, mut rank) = (None, None);
+        for symbol in qualifier.bytes() {
+            match symbol {
+                b'a'..=b'h' if file.is_none() && rank.is_none() => file = Some(symbol - b'a'),
+                b'1'..=b'8' if rank.is_none() => rank = Some(symbol - b'1'),
+                _ => return Err(invalid()),
+            }
+        }
+
+        let candidates: Vec<Move> = state
+            .get_pieces()
+            .filter(|piece| piece.get_player() == player && piece.get_type() == piece_type)
+            .map(|piece| piece.get_position())
+            .filter(|position| file.is_none_or(|file| position.get_column() == file))
+            .filter(|position| rank.is_none_or(|rank| position.get_row() == rank))
+            .flat_map(|position| state.get_moves(position))
+            .filter(|r#move| r#move.end_position == end_position)
+            .collect();
+        // A promotion is generated as one move per piece, all of them
+        // sharing the squares.
+        let is_promotion = candidates.iter().any(|r#move| r#move.promotion.is_some());
+        match (is_promotion, promotion) {
+            (true, None) => return Err(format!("Missing promotion piece in '{}'", san)),
+            (false, Some(_)) if !candidates.is_empty() => {
+                return Err(format!("'{}' is not a promotion move", san))
+            }
+            _ => (),
+        }
+        let candidates: Vec<Move> = candidates
+            .into_iter()
+            .filter(|r#move| r#move.promotion == promotion)
+            .collect();
+        match candidates[..] {
+            [r#move] => Ok(r#move),
+            [] => Err(format!("No legal move matches '{}'", san)),
+            _ => Err(format!("Ambiguous move '{}'", san)),
+        }
+    }
+
+    /// Returns the move written in the UCI long algebraic notation, e.g.
+    /// "e2e4" or "e7e8q". Castling is written as the move of the king.
+    pub fn to_uci(&self) -> String {
+        let mut result = format!("{}{}", self.current_position, self.end_position);
+        if let Some(promotion_type) = self.promotion {
+            result.push(
+                promotion_type
+                    .get_piece_type()
+                    .to_char()
+                    .to_ascii_lowercase(),
+            );
+        }
+        result
+    }
+
+    /// Parses a move written in the UCI long algebraic notation. Only the
+    /// syntax is checked, the move doesn't have to be legal and carries no
+    /// flags.
+    pub fn from_uci(uci: &str) -> Result<Move, String> {
+        let invalid = || format!("Invalid UCI move '{}'", uci);
+        if !uci.is_ascii() || !(4..=5).contains(&uci.len()) {
+            return Err(invalid());
+        }
+        let from = uci[0..2].parse().map_err(|_| invalid())?;
+        let to = uci[2..4].parse().map_err(|_| invalid())?;
+        let promotion = match uci[4..].chars().next() {
+            Some(letter) if letter.is_ascii_lowercase() => Some(
+                PieceType::from_char(letter)
+                    .and_then(PromotionType::from_piece_type)
+                    .ok_or_else(invalid)?,
+            ),
+            Some(_) => return Err(invalid()),
+            None => None,
+        };
+        Ok(Move::new(from, to).with_promotion(promotion))
+    }
+}
+
+impl PartialEq for Move {
+    fn eq(&self, other: &Self) -> bool {
+        self.get_key() == other.get_key()
+    }
+}
+
+impl Eq for Move {}
+
+impl PartialOrd for Move {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Move {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.get_key().cmp(&other.get_key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san(fen: &str, from: &str, to: &str, promotion: Option<PromotionType>) -> String {
+        let state = GameState::from_fen(fen).unwrap();
+        let wanted =
+            Move::new(from.parse().unwrap(), to.parse().unwrap()).with_promotion(promotion);
+        let r#move = state
+            .get_moves(wanted.get_current_position())
+            .into_iter()
+            .find(|r#move| *r#move == wanted)
+            .expect("The move should be legal");
+        r#move.to_san(&state)
+    }
+
+    fn parse(fen: &str, san: &str) -> Result<(String, Option<PromotionType>), String> {
+        let state = GameState::from_fen(fen).unwrap();
+        Move::from_san(&state, san).map(|r#move| {
+            (
+                format!("{}{}", r#move.current_position, r#move.end_position),
+                r#move.promotion,
+            )
+        })
+    }
+
+    #[test]
+    fn test_san_parsing() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(parse(fen, "e4"), Ok(("e2e4".to_string(), None)));
+        assert_eq!(parse(fen, "Nf3!?"), Ok(("g1f3".to_string(), None)));
+        assert_eq!(parse(fen, "Ngf3"), Ok(("g1f3".to_string(), None)));
+        assert_eq!(
+            parse(fen, "Nf4"),
+            Err("No legal move matches 'Nf4'".to_string())
+        );
+        assert_eq!(parse(fen, "Zf3"), Err("Invalid SAN move 'Zf3'".to_string()));
+        assert_eq!(parse(fen, "e9"), Err("Invalid SAN move 'e9'".to_string()));
+        assert_eq!(
+            parse(fen, "O-O"),
+            Err("Castling is not possible in 'O-O'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_san_parsing_disambiguation() {
+        let fen = "4k3/8/8/R7/8/8/4K3/R6R w - - 0 1";
+        assert_eq!(parse(fen, "Rd1"), Err("Ambiguous move 'Rd1'".to_string()));
+        assert_eq!(parse(fen, "Rad1"), Ok(("a1d1".to_string(), None)));
+        assert_eq!(parse(fen, "Rhd1"), Ok(("h1d1".to_string(), None)));
+        assert_eq!(parse(fen, "Ra3"), Err("Ambiguous move 'Ra3'".to_string()));
+        assert_eq!(parse(fen, "R5a3"), Ok(("a5a3".to_string(), None)));
+        assert_eq!(parse(fen, "Ra1a3"), Ok(("a1a3".to_string(), None)));
+    }
+
+    #[test]
+    fn test_san_parsing_special_moves() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1";
+        assert_eq!(parse(fen, "O-O"), Ok(("e8g8".to_string(), None)));
+        assert_eq!(parse(fen, "0-0-0"), Ok(("e8c8".to_string(), None)));
+        let fen = "1r5k/P7/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        assert_eq!(parse(fen, "exd6"), Ok(("e5d6".to_string(), None)));
+        assert_eq!(
+            parse(fen, "axb8=N+"),
+            Ok(("a7b8".to_string(), Some(PromotionType::Knight)))
+        );
+        assert_eq!(
+            parse(fen, "a8=Q"),
+            Ok(("a7a8".to_string(), Some(PromotionType::Queen)))
+        );
+        assert_eq!(
+            parse(fen, "a8"),
+            Err("Missing promotion piece in 'a8'".to_string())
+        );
+        assert_eq!(
+            parse(fen, "e6=Q"),
+            Err("'e6=Q' is not a promotion move".to_string())
+        );
+        assert_eq!(
+            parse(fen, "a8=K"),
+            Err("Invalid SAN move 'a8=K'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_san_simple_moves() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(san(fen, "e2", "e4", None), "e4");
+        assert_eq!(san(fen, "g1", "f3", None), "Nf3");
+    }
+
+    #[test]
+    fn test_san_captures() {
+        let fen = "4k3/8/8/3pP3/8/8/8/4K1N1 w - d6 0 1";
+        assert_eq!(san(fen, "e5", "d6", None), "exd6");
+        assert_eq!(san(fen, "g1", "h3", None), "Nh3");
+        let fen = "4k3/8/8/3p4/4P3/8/5r2/4K3 w - - 0 1";
+        assert_eq!(san(fen, "e4", "d5", None), "exd5");
+        assert_eq!(san(fen, "e1", "f2", None), "Kxf2");
+    }
+
+    #[test]
+    fn test_san_disambiguation() {
+        assert_eq!(
+            san("4k3/8/8/8/8/8/4K3/R6R w - - 0 1", "a1", "d1", None),
+            "Rad1"
+        );
+        assert_eq!(
+            san("4k3/8/8/R7/8/8/4K3/R7 w - - 0 1", "a1", "a3", None),
+            "R1a3"
+        );
+        assert_eq!(
+            san("8/8/8/7k/8/Q7/4K3/Q1Q5 w - - 0 1", "a1", "b2", None),
+            "Qa1b2"
+        );
+        // A rival pinned to its king doesn't count.
+        assert_eq!(
+            san("1k2r3/8/8/8/8/8/R3R3/4K3 w - - 0 1", "a2", "d2", None),
+            "Rd2"
+        );
+    }
+
+    #[test]
+    fn test_san_castling_promotion_and_checks() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(san(fen, "e1", "g1", None), "O-O");
+        assert_eq!(san(fen, "e1", "c1", None), "O-O-O");
+        let fen = "7k/4P3/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(san(fen, "e7", "e8", Some(PromotionType::Queen)), "e8=Q+");
+        assert_eq!(san(fen, "e7", "e8", Some(PromotionType::Knight)), "e8=N");
+        let fen = "6k1/5ppp/8/8/8/8/8/R3K3 w Q - 0 1";
+        assert_eq!(san(fen, "a1", "a8", None), "Ra8#");
+        assert_eq!(san(fen, "e1", "c1", None), "O-O-O");
+    }
+
+    #[test]
+    fn test_uci() {
+        let r#move = Move::new("e7".parse().unwrap(), "e8".parse().unwrap());
+        let promotion = r#move.with_promotion(Some(PromotionType::Knight));
+        assert_eq!(r#move.to_uci(), "e7e8");
+        assert_eq!(promotion.to_uci(), "e7e8n");
+        assert_eq!(Move::from_uci("e7e8"), Ok(r#move));
+        assert_eq!(Move::from_uci("e7e8n"), Ok(promotion));
+        assert_eq!(
+            Move::from_uci("e1g1"),
+            Ok(Move::new("e1".parse().unwrap(), "g1".parse().unwrap()))
+        );
+        for uci in ["", "e2", "e2e9", "i2e4", "e7e8k", "e7e8Q", "e2e4 ", "é2e4"] {
+            assert_eq!(
+                Move::from_uci(uci),
+                Err(format!("Invalid UCI move '{}'", uci))
+            );
+        }
+    }
+
+    #[test]
+    fn test_move_flags() {
+        let state = GameState::from_fen("r3k3/1P6/8/3pP3/8/8/4P3/4K2R w Kq d6 0 1").unwrap();
+        let find = |from: &str, to: &str| {
+            let wanted = Move::new(from.parse().unwrap(), to.parse().unwrap());
+            state
+                .get_moves(wanted.get_current_position())
+                .into_iter()
+                .find(|r#move| r#move.end_position == wanted.end_position)
+                .unwrap()
+        };
+        let double_push = find("e2", "e4");
+        assert!(double_push.is_double_push() && !double_push.is_capture());
+        let en_passant = find("e5", "d6");
+        assert!(en_passant.is_en_passant() && en_passant.is_capture());
+        let castle = find("e1", "g1");
+        assert!(castle.is_castle() && !castle.is_capture());
+        let capture = find("b7", "a8");
+        assert!(capture.is_capture() && !capture.is_en_passant());
+        assert!(capture.get_promotion().is_some());
+        let promotions: Vec<Option<PromotionType>> = state
+            .get_moves("b7".parse().unwrap())
+            .iter()
+            .filter(|r#move| r#move.end_position == "b8".parse().unwrap())
+            .map(|r#move| r#move.get_promotion())
+            .collect();
+        assert_eq!(promotions, PromotionType::ALL.map(Some),);
+        let quiet = find("h1", "h5");
+        assert_eq!(quiet.flags, 0);
+    }
 }
@@ -0,0 +1,595 @@
+use crate::{GameResult, Player};
+use itertools::Itertools;
+
+/// The tags every exported game has to contain, in the order they have to be
+/// written in.
+pub const SEVEN_TAG_ROSTER: [&str; 7] =
+    ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// The maximal number of characters in a line of exported movetext.
+const MAX_LINE_LENGTH: usize = 79;
+
+/// Returns the value a tag of the Seven Tag Roster gets if it wasn't set.
+pub fn get_default_tag_value(name: &str) -> &'static str {
+    match name {
+        "Date" => "????.??.??",
+        "Result" => "*",
+        _ => "?",
+    }
+}
+
+/// Returns the token denoting a given result in PGN: "1-0", "0-1", "1/2-1/2"
+/// or "*" for a game that is still in progress.
+pub fn get_result_token(result: GameResult) -> &'static str {
+    match result {
+        GameResult::Ongoing => "*",
+        _ => match result.get_winner() {
+            Some(Player::White) => "1-0",
+            Some(Player::Black) => "0-1",
+            None => "1/2-1/2",
+        },
+    }
+}
+
+/// Returns the value of the "Termination" tag describing how a given result
+/// was reached.
+pub fn get_termination(result: GameResult) -> &'static str {
+    match result {
+        GameResult::Ongoing => "unterminated",
+        GameResult::Timeout(_) | GameResult::DrawByTimeout => "time forfeit",
+        _ => "normal",
+    }
+}
+
+/// Returns a game written in the export format of the Portable Game Notation.
+/// The tags are written in the given order; the movetext starts from a given
+/// move number and player.
+pub fn export_pgn(
+    tags: &[(String, String)],
+    first_move_number: u32,
+    first_player: Player,
+    movetext: &[MovetextElement],
+    result_token: &str,
+) -> String {
+    let tag_section = tags
+        .iter()
+        .map(|(name, value)| {
+            format!(
+                "[{} \"{}\"]",
+                name,
+                value.replace('\\', "\\\\").replace('"', "\\\"")
+            )
+        })
+        .join("\n");
+
+    let ply_offset = if first_player == Player::Black { 1 } else { 0 };
+    let mut tokens = vec![];
+    push_movetext_tokens(
+        &mut tokens,
+        movetext,
+        (first_move_number.max(1) - 1) * 2 + ply_offset,
+    );
+    tokens.push(result_token.to_string());
+
+    format!("{}\n\n{}\n", tag_section, wrap_tokens(&tokens))
+}
+
+/// Appends the tokens of the movetext whose first move is made in a given
+/// ply, counted from the first move of White. A move of Black gets its number
+/// written only if it starts a line or follows a comment or a variation.
+fn push_movetext_tokens(tokens: &mut Vec<String>, movetext: &[MovetextElement], first_ply: u32) {
+    let mut ply = first_ply;
+    let mut needs_number = true;
+    for element in movetext {
+        match element {
+            MovetextElement::Move(san) => {
+                let move_number = ply / 2 + 1;
+                if ply.is_multiple_of(2) {
+                    tokens.push(format!("{}.", move_number));
+                } else if needs_number {
+                    tokens.push(format!("{}...", move_number));
+                }
+                tokens.push(san.clone());
+                ply += 1;
+                needs_number = false;
+            }
+            MovetextElement::Comment(comment) => {
+                // The words are separate tokens, so that long comments can be
+                // broken into lines.
+                let comment = format!("{{{}}}", comment.replace('}', ")"));
+                tokens.extend(comment.split_whitespace().map(String::from));
+                needs_number = true;
+            }
+            MovetextElement::Nag(nag) => tokens.push(format!("${}", nag)),
+            MovetextElement::Variation(variation) => {
+                let mut variation_tokens = vec![];
+                push_movetext_tokens(&mut variation_tokens, variation, ply.saturating_sub(1));
+                if variation_tokens.is_empty() {
+                    tokens.push("()".to_string());
+                } else {
+                    variation_tokens[0].insert(0, '(');
+                    variation_tokens.last_mut().unwrap().push(')');
+                    tokens.append(&mut variation_tokens);
+                }
+                needs_number = true;
+            }
+        }
+    }
+}
+
+/// Joins the tokens with spaces, breaking the lines so that none of them is
+/// longer than MAX_LINE_LENGTH characters.
+fn wrap_tokens(tokens: &[String]) -> String {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(token);
+    }
+    lines.push(line);
+    lines.join("\n")
+}
+
+/// A single element of the movetext of a game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovetextElement {
+    /// A move written in SAN.
+    Move(String),
+    /// A comment, either in braces or until the end of the line.
+    Comment(String),
+    /// A Numeric Annotation Glyph; suffix annotations such as "!?" are
+    /// converted into NAGs as well.
+    Nag(u8),
+    /// An alternative to the move preceding it.
+    Variation(Vec<MovetextElement>),
+}
+
+/// A single game read from a PGN file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PgnGame {
+    /// The tags of the game, in the order they were written in.
+    pub tags: Vec<(String, String)>,
+    /// The movetext of the game, including comments and variations.
+    pub movetext: Vec<MovetextElement>,
+    /// The game termination marker: "1-0", "0-1", "1/2-1/2" or "*".
+    pub result: String,
+}
+
+impl PgnGame {
+    /// Returns the value of a given tag.
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+    /// Returns the moves of the main line, i.e. those outside of variations.
+    pub fn get_main_line(&self) -> Vec<&str> {
+        self.movetext
+            .iter()
+            .filter_map(|element| match element {
+                MovetextElement::Move(san) => Some(san.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    StartVariation,
+    EndVariation,
+    Move(String),
+    Result(String),
+}
+
+/// Splits the text of a PGN file into tokens.
+struct Lexer<'a> {
+    text: &'a str,
+    offset: usize,
+    line: usize,
+    pending: Vec<Token>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            offset: 0,
+            line: 1,
+            pending: vec![],
+        }
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.text[self.offset..].chars().next()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let symbol = self.peek_char()?;
+        self.offset += symbol.len_utf8();
+        if symbol == '\n' {
+            self.line += 1;
+        }
+        Some(symbol)
+    }
+
+    /// Consumes characters as long as they satisfy a predicate and returns
+    /// them.
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.offset;
+        while self.peek_char().is_some_and(&predicate) {
+            self.next_char();
+        }
+        &self.text[start..self.offset]
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("Line {}: {}", self.line, message)
+    }
+
+    fn push_back(&mut self, token: Token) {
+        self.pending.push(token);
+    }
+
+    fn next_token(&mut self) -> Option<Result<Token, String>> {
+        if let Some(token) = self.pending.pop() {
+            return Some(Ok(token));
+        }
+        loop {
+            let at_line_start = self.offset == 0 || self.text[..self.offset].ends_with('\n');
+            match self.peek_char()? {
+                symbol if symbol.is_whitespace() => {
+                    self.next_char();
+                }
+                // Lines starting with a percent sign are escaped and ignored.
+                '%' if at_line_start => {
+                    self.take_while(|symbol| symbol != '\n');
+                }
+                _ => break,
+            }
+        }
+        if !"[{;()*$".contains(self.peek_char()?) {
+            let symbol =
+                self.take_while(|symbol| !symbol.is_whitespace() && !"[]{}();$*".contains(symbol));
+            return Some(self.read_symbol(symbol));
+        }
+        let token = match self.next_char()? {
+            '[' => self.read_tag(),
+            '{' => {
+                let comment = self.take_while(|symbol| symbol != '}');
+                match self.next_char() {
+                    Some('}') => Ok(Token::Comment(comment.trim().to_string())),
+                    _ => Err(self.error("Unterminated comment")),
+                }
+            }
+            ';' => Ok(Token::Comment(
+                self.take_while(|symbol| symbol != '\n').trim().to_string(),
+            )),
+            '(' => Ok(Token::StartVariation),
+            ')' => Ok(Token::EndVariation),
+            '*' => Ok(Token::Result("*".to_string())),
+            '$' => self
+                .take_while(|symbol| symbol.is_ascii_digit())
+                .parse()
+                .map(Token::Nag)
+                .map_err(|_| self.error("Invalid NAG")),
+            _ => unreachable!(),
+        };
+        Some(token)
+    }
+
+    fn read_tag(&mut self) -> Result<Token, String> {
+        self.take_while(char::is_whitespace);
+        let name = self.take_while(|symbol| symbol.is_ascii_alphanumeric() || symbol == '_');
+        self.take_while(char::is_whitespace);
+        if name.is_empty() || self.next_char() != Some('"') {
+            return Err(self.error("Malformed tag pair"));
+        }
+        let mut value = String::new();
+        let mut escaped = false;
+        loop {
+            // The end of the line is only peeked at, so the error is reported
+            // on the line of the tag.
+            let symbol = match self.peek_char() {
+                Some('\n') | None => return Err(self.error("Unterminated tag value")),
+                Some(symbol) => symbol,
+            };
+            self.next_char();
+            match symbol {
+                '"' if !escaped => break,
+                '\\' if !escaped => escaped = true,
+                _ => {
+                    value.push(symbol);
+                    escaped = false;
+                }
+            }
+        }
+        self.take_while(char::is_whitespace);
+        if self.next_char() != Some(']') {
+            return Err(self.error("Malformed tag pair"));
+        }
+        Ok(Token::Tag(name.to_string(), value))
+    }
+
+    /// Classifies a symbol found in the movetext: a move number, a result or
+    /// a move, possibly followed by a suffix annotation.
+    fn read_symbol(&mut self, symbol: &str) -> Result<Token, String> {
+        if symbol.is_empty() {
+            return Err(self.error("Unexpected character"));
+        }
+        if ["1-0", "0-1", "1/2-1/2"].contains(&symbol) {
+            return Ok(Token::Result(symbol.to_string()));
+        }
+        // Move numbers may be glued to the move itself, e.g. "1.e4".
+        let mut san = symbol;
+        if !san.starts_with("0-0") {
+            let digits = san.trim_start_matches(|symbol: char| symbol.is_ascii_digit());
+            if digits.len() < san.len() {
+                san = digits.trim_start_matches('.');
+                if san.is_empty() {
+                    return match self.next_token() {
+                        Some(token) => token,
+                        None => Err(self.error("Unexpected end of the movetext")),
+                    };
+                }
+            }
+        }
+        let annotation_start = san.find(['!', '?']).unwrap_or(san.len());
+        let (san, annotation) = san.split_at(annotation_start);
+        let nag = match annotation {
+            "" => None,
+            "!" => Some(1),
+            "?" => Some(2),
+            "!!" => Some(3),
+            "??" => Some(4),
+            "!?" => Some(5),
+            "?!" => Some(6),
+            _ => return Err(self.error(&format!("Invalid annotation '{}'", annotation))),
+        };
+        if let Some(nag) = nag {
+            self.push_back(Token::Nag(nag));
+        }
+        Ok(Token::Move(san.to_string()))
+    }
+}
+
+/// An iterator over the games stored in a PGN file. Reading stops after the
+/// first malformed game.
+pub struct PgnGames<'a> {
+    lexer: Lexer<'a>,
+    failed: bool,
+}
+
+impl<'a> PgnGames<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self {
+            lexer: Lexer::new(text),
+            failed: false,
+        }
+    }
+
+    fn read_game(&mut self) -> Option<Result<PgnGame, String>> {
+        let mut game = PgnGame {
+            result: "*".to_string(),
+            ..Default::default()
+        };
+        let mut started = false;
+        // The innermost variation is on top of the stack; the bottom holds the
+        // main line.
+        let mut stack: Vec<Vec<MovetextElement>> = vec![vec![]];
+        while let Some(token) = self.lexer.next_token() {
+            let token = match token {
+                Ok(token) => token,
+                Err(error) => return Some(Err(error)),
+            };
+            started = true;
+            let element = match token {
+                Token::Tag(name, value) => {
+                    if stack.len() > 1 || !stack[0].is_empty() {
+                        // A new game starts without the previous one having
+                        // been terminated.
+                        self.lexer.push_back(Token::Tag(name, value));
+                        break;
+                    }
+                    game.tags.push((name, value));
+                    continue;
+                }
+                Token::Comment(comment) => MovetextElement::Comment(comment),
+                Token::Nag(nag) => MovetextElement::Nag(nag),
+                Token::Move(san) => MovetextElement::Move(san),
+                Token::StartVariation => {
+                    stack.push(vec![]);
+                    continue;
+                }
+                Token::EndVariation => match stack.pop() {
+                    Some(variation) if !stack.is_empty() => MovetextElement::Variation(variation),
+                    _ => return Some(Err(self.lexer.error("Unmatched ')'"))),
+                },
+                Token::Result(result) => {
+                    if stack.len() > 1 {
+                        return Some(Err(self.lexer.error("Unterminated variation")));
+                    }
+                    game.result = result;
+                    break;
+                }
+            };
+            stack.last_mut().unwrap().push(element);
+        }
+        if !started {
+            return None;
+        }
+        if stack.len() > 1 {
+            return Some(Err(self.lexer.error("Unterminated variation")));
+        }
+        game.movetext = stack.pop().unwrap();
+        Some(Ok(game))
+    }
+}
+
+impl Iterator for PgnGames<'_> {
+    type Item = Result<PgnGame, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let game = self.read_game();
+        self.failed = matches!(game, Some(Err(_)));
+        game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_tags(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_export_pgn() {
+        let tags = make_tags(&[("Event", "Casual \"blitz\""), ("Result", "1-0")]);
+        let moves = moves(&["e4", "e5", "Qh5", "Nc6", "Bc4", "Nf6", "Qxf7#"]);
+        let pgn = export_pgn(&tags, 1, Player::White, &moves, "1-0");
+        assert_eq!(
+            pgn,
+            "[Event \"Casual \\\"blitz\\\"\"]\n[Result \"1-0\"]\n\n\
+             1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0\n"
+        );
+    }
+
+    #[test]
+    fn test_export_pgn_black_to_move() {
+        let moves = moves(&["Nf6", "Nc3"]);
+        let pgn = export_pgn(&[], 12, Player::Black, &moves, "*");
+        assert_eq!(pgn, "\n\n12... Nf6 13. Nc3 *\n");
+    }
+
+    #[test]
+    fn test_export_pgn_annotations() {
+        let mut variation = moves(&["c5", "Nf3"]);
+        variation.insert(1, MovetextElement::Comment("Sicilian".to_string()));
+        variation.push(MovetextElement::Variation(moves(&["c3"])));
+        let mut movetext = moves(&["e4", "e5", "Nf3"]);
+        movetext.insert(2, MovetextElement::Nag(1));
+        movetext.insert(3, MovetextElement::Variation(variation));
+        movetext.insert(0, MovetextElement::Comment("A {short} game".to_string()));
+        let pgn = export_pgn(&[], 1, Player::White, &movetext, "*");
+        assert_eq!(
+            pgn,
+            "\n\n{A {short) game} 1. e4 e5 $1 (1... c5 {Sicilian} 2. Nf3 (2. c3)) 2. Nf3 *\n"
+        );
+    }
+
+    #[test]
+    fn test_line_wrapping() {
+        let moves = moves(&["Nf3"; 60]);
+        let pgn = export_pgn(&[], 1, Player::White, &moves, "*");
+        let movetext = pgn.trim_start_matches('\n');
+        assert!(movetext.lines().count() > 1);
+        assert!(movetext.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert_eq!(
+            movetext.split_whitespace().count(),
+            moves.len() + moves.len() / 2 + 1
+        );
+    }
+
+    fn moves(sans: &[&str]) -> Vec<MovetextElement> {
+        sans.iter()
+            .map(|san| MovetextElement::Move(san.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_read_game() {
+        let text = "[Event \"Test \\\"quoted\\\"\"]\n[White \"A\"]\n\n\
+                    1. e4 {Best by test} e5 $1 2.Nf3 Nc6?! (2... d6 3. d4 (3. Bc4)) ; rest\n\
+                    3. Bb5 1-0\n";
+        let games: Vec<_> = PgnGames::new(text).collect();
+        assert_eq!(games.len(), 1);
+        let game = games[0].clone().unwrap();
+        assert_eq!(game.get_tag("Event"), Some("Test \"quoted\""));
+        assert_eq!(game.get_tag("White"), Some("A"));
+        assert_eq!(game.result, "1-0");
+        assert_eq!(game.get_main_line(), ["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+        let mut inner = moves(&["d6", "d4"]);
+        inner.push(MovetextElement::Variation(moves(&["Bc4"])));
+        assert_eq!(
+            game.movetext,
+            vec![
+                MovetextElement::Move("e4".to_string()),
+                MovetextElement::Comment("Best by test".to_string()),
+                MovetextElement::Move("e5".to_string()),
+                MovetextElement::Nag(1),
+                MovetextElement::Move("Nf3".to_string()),
+                MovetextElement::Move("Nc6".to_string()),
+                MovetextElement::Nag(6),
+                MovetextElement::Variation(inner),
+                MovetextElement::Comment("rest".to_string()),
+                MovetextElement::Move("Bb5".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_multiple_games() {
+        let text = "% exported by a test\n[Event \"1\"]\n\n1. d4 d5 1/2-1/2\n\n\
+                    [Event \"2\"]\n\n1. c4 *\n[Event \"3\"]\n1. O-O\n[Event \"4\"]\n";
+        let games: Vec<PgnGame> = PgnGames::new(text).map(Result::unwrap).collect();
+        assert_eq!(games.len(), 4);
+        assert_eq!(games[0].result, "1/2-1/2");
+        assert_eq!(games[0].get_main_line(), ["d4", "d5"]);
+        assert_eq!(games[1].get_tag("Event"), Some("2"));
+        assert_eq!(games[1].get_main_line(), ["c4"]);
+        assert_eq!(games[2].result, "*");
+        assert_eq!(games[2].get_main_line(), ["O-O"]);
+        assert!(games[3].movetext.is_empty());
+        assert_eq!(games[3].get_tag("Event"), Some("4"));
+    }
+
+    #[test]
+    fn test_read_errors() {
+        for (text, error) in [
+            ("1. e4 {unfinished", "Line 1: Unterminated comment"),
+            ("1. e4 e5 ) 2. Nf3", "Line 1: Unmatched ')'"),
+            ("1. e4 (1. d4 *", "Line 1: Unterminated variation"),
+            ("1. e4 (1. d4", "Line 1: Unterminated variation"),
+            ("[Event \"x]\n1. e4", "Line 1: Unterminated tag value"),
+            ("[Event \"x\\\n\"]", "Line 1: Unterminated tag value"),
+            ("\n[Event \"x", "Line 2: Unterminated tag value"),
+            ("[Event x]", "Line 1: Malformed tag pair"),
+            ("1. e4!!! e5", "Line 1: Invalid annotation '!!!'"),
+        ] {
+            let mut games = PgnGames::new(text);
+            assert_eq!(games.next(), Some(Err(error.to_string())), "{}", text);
+            assert_eq!(games.next(), None);
+        }
+    }
+
+    #[test]
+    fn test_result_token() {
+        assert_eq!(get_result_token(GameResult::Ongoing), "*");
+        assert_eq!(
+            get_result_token(GameResult::Checkmate(Player::White)),
+            "1-0"
+        );
+        assert_eq!(
+            get_result_token(GameResult::Resignation(Player::Black)),
+            "0-1"
+        );
+        assert_eq!(get_result_token(GameResult::Stalemate), "1/2-1/2");
+    }
+}
@@ -2,12 +2,11 @@ use crate::gamestate::GameState;
 pub use crate::player::Player;
 pub use crate::position::Position;
 pub use crate::r#move::Move;
-use itertools::{iproduct, Itertools};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
 #[repr(u8)]
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum PieceType {
     Pawn,
     Knight,
@@ -17,6 +16,43 @@ pub enum PieceType {
     King,
 }
 
+impl PieceType {
+    /// All the piece types, from the pawn to the king.
+    pub const ALL: [PieceType; 6] = [
+        Self::Pawn,
+        Self::Knight,
+        Self::Bishop,
+        Self::Rook,
+        Self::Queen,
+        Self::King,
+    ];
+    /// Returns the uppercase letter denoting the piece type in FEN and
+    /// algebraic notation.
+    pub fn to_char(&self) -> char {
+        match self {
+            Self::Pawn => 'P',
+            Self::Knight => 'N',
+            Self::Bishop => 'B',
+            Self::Rook => 'R',
+            Self::Queen => 'Q',
+            Self::King => 'K',
+        }
+    }
+    /// Returns the piece type denoted by a given letter, regardless of its
+    /// case.
+    pub fn from_char(symbol: char) -> Option<Self> {
+        match symbol.to_ascii_uppercase() {
+            'P' => Some(Self::Pawn),
+            'N' => Some(Self::Knight),
+            'B' => Some(Self::Bishop),
+            'R' => Some(Self::Rook),
+            'Q' => Some(Self::Queen),
+            'K' => Some(Self::King),
+            _ => None,
+        }
+    }
+}
+
 /// This struct contains data that is present in every Piece enum variant,
 /// namely the position of a piece and which player does it belong to.
 #[derive(Clone, Copy, Debug)]
@@ -108,6 +144,34 @@ impl Piece {
         }
     }
 
+    /// Creates a piece of a given type. The pawn's first move flag and the
+    /// castling flag of the king and the rook are set as given.
+    pub fn new_of_type(
+        piece_type: PieceType,
+        position: Position,
+        player: Player,
+        flag: bool,
+    ) -> Self {
+        match piece_type {
+            PieceType::Pawn => Self::new_pawn(position, player, flag),
+            PieceType::Knight => Self::new_knight(position, player),
+            PieceType::Bishop => Self::new_bishop(position, player),
+            PieceType::Rook => Self::new_rook(position, player, flag),
+            PieceType::Queen => Self::new_queen(position, player),
+            PieceType::King => Self::new_king(position, player, flag),
+        }
+    }
+
+    /// Returns the letter denoting the piece in FEN: uppercase for white
+    /// pieces, lowercase for black ones.
+    pub fn to_fen_char(&self) -> char {
+        let symbol = self.get_type().to_char();
+        match self.get_player() {
+            Player::White => symbol,
+            Player::Black => symbol.to_ascii_lowercase(),
+        }
+    }
+
     /// Returns a PieceType value according to the piece's type.
     pub fn get_type(&self) -> PieceType {
         match *self {
@@ -156,8 +220,6 @@ impl Piece {
             ) => player,
         };
         PieceData::new(player, self.get_type())
-
-
     }
 
     pub fn set_position(&mut self, new_position: &Position) {
@@ -171,6 +233,20 @@ impl Piece {
         }
     }
 
+    /// Returns a copy of the piece placed on a new position. Since the piece
+    /// has now moved, a pawn loses its double step and a king or a rook is no
+    /// longer eligible for castling.
+    pub fn moved_to(&self, new_position: Position) -> Self {
+        let mut result = match *self {
+            Self::Pawn(data, _) => Self::Pawn(data, false),
+            Self::Rook(data, _) => Self::Rook(data, false),
+            Self::King(data, _) => Self::King(data, false),
+            other => other,
+        };
+        result.set_position(&new_position);
+        result
+    }
+
     /// Returns true if a pawn is in a circumstance where it can be taken
     /// en-passant by another pawn.
     // pub fn is_en_passantable(&self) -> bool {
@@ -197,196 +273,13 @@ impl Piece {
             unreachable!("This method should be used on a pawn.")
         }
     }
-    fn get_moves_lines(&self, directions: Vec<(i32, i32)>, state: &GameState) -> Vec<Move> {
-        let mut result: Vec<Move> = vec![];
-        for (dir_column, dir_row) in directions {
-            for distance in 1..8 {
-                let new_column =
-                    ((self.get_position().get_column() as i32) + distance * dir_column) as u8;
-                let new_row = ((self.get_position().get_row() as i32) + distance * dir_row) as u8;
-                if let Some(position) = Position::new(new_row, new_column) {
-                    if let Some(other_piece) = state.get_piece(position) {
-                        if other_piece.get_player() != self.get_player() {
-                            result.push(Move::new(self.get_position(), position));
-                        }
-                        break;
-                    } else {
-                        result.push(Move::new(self.get_position(), position));
-                    }
-                }
-            }
-        }
-        result
-    }
-    fn get_moves_shifts(&self, shifts: Vec<(i32, i32)>, state: &GameState) -> Vec<Move> {
-        let mut result: Vec<Move> = vec![];
-        for (row_shift, column_shift) in shifts {
-            let new_column = (self.get_position().get_column() as i32 + column_shift) as u8;
-            let new_row = (self.get_position().get_row() as i32 + row_shift) as u8;
-            if let Some(new_pos) = Position::new(new_row, new_column) {
-                if let Some(other_piece) = state.get_piece(new_pos) {
-                    if other_piece.get_player() != self.get_player() {
-                        result.push(Move::new(self.get_position(), new_pos));
-                    }
-                } else {
-                    result.push(Move::new(self.get_position(), new_pos));
-                }
-            }
-        }
-        result
-    }
-    fn check_castle_move(&self, state: &GameState, right_side: bool) -> Option<Move> {
-        let (row, _) = self.get_position().as_tuple();
-        if let Some(Self::Rook(
-            SharedData {
-                position: _,
-                player,
-            },
-            true,
-        )) = state.get_piece(Position::new(row, if right_side { 7 } else { 0 }).unwrap())
-        {
-            if *player == self.get_player()
-                && if right_side { 5..=6 } else { 1..=3 }
-                    .map(|idx| state.get_piece(Position::new(row, idx).unwrap()))
-                    .all(|piece| piece.is_none())
-            {
-                return Some(Move::new(
-                    self.get_position(),
-                    Position::new(row, if right_side { 6 } else { 2 }).unwrap(),
-                ));
-            }
-        }
-        None
-    }
     /// Returns a vector of moves possible to make for a given piece given a
-    /// game state object. The resulting moves are guaranteed to be legal
-    /// according to the rules of the game.
+    /// game state object. The resulting moves are pseudo-legal, meaning they
+    /// follow the movement rules of the piece, but they may still leave the
+    /// player's own king in check; see `GameState::get_moves` for the legal
+    /// ones.
     pub fn get_moves(&self, state: &GameState) -> Vec<Move> {
-        match *self {
-            Self::Pawn(_, first_move) => {
-                let (row, col) = self.get_position().as_tuple();
-                println!("row: {}", row);
-                println!("col: {}", col);
-                println!("{:?}", self.get_player());
-                let idx: i8;
-                if self.get_player() == Player::White {
-                    idx = 1;
-                } else {
-                    idx = -1;
-                }
-                println!("idx: {}", idx);
-                println!("first move: {}", first_move);
-                let mut result = vec![];
-                if let Some(to_pos) = Position::new((row as i8 + idx).try_into().unwrap(), col) {
-                    println!("to_pos: {:?}", to_pos);
-                    if let None = state.get_piece(to_pos) {
-                        println!("pushing");
-                        result.push(Move::new(self.get_position(), to_pos));
-                    }
-                }
-                if first_move {
-                    if let Some(to_pos) =
-                        Position::new((row as i8 + 2 * idx).try_into().unwrap(), col)
-                    {
-                        if let None = state.get_piece(to_pos) {
-                            result.push(Move::new(self.get_position(), to_pos));
-                        }
-                    }
-                }
-                if col >= 1 {
-                    if let Some(to_pos) =
-                        Position::new((row as i8 + idx).try_into().unwrap(), col - 1)
-                    {
-                        if let Some(piece) = state.get_piece(to_pos) {
-                            if piece.get_player() != self.get_player() {
-                                result.push(Move::new(self.get_position(), to_pos));
-                            }
-                        }
-                    }
-                    if let Some(piece) = state.get_piece(Position::new(row, col - 1).unwrap()) {
-                        if state.get_en_passant_square()
-                            == Some(&Position::new(row, col - 1).unwrap())
-                        {
-                            println!("en passant");
-                            if piece.get_player() != self.get_player() {
-                                println!("pushing");
-                                if let Some(to_pos) =
-                                    Position::new((row as i8 + idx).try_into().unwrap(), col - 1)
-                                {
-                                    result.push(Move::new(self.get_position(), to_pos));
-                                }
-                            }
-                        }
-                    }
-                }
-                if col <= 6 {
-                    if let Some(to_pos) = Position::new((row as i8 + idx).try_into().unwrap(), col + 1)
-                    {
-                        if let Some(piece) = state.get_piece(to_pos) {
-                            if piece.get_player() != self.get_player() {
-                                result.push(Move::new(self.get_position(), to_pos));
-                            }
-                        }
-                    }
-
-                    if let Some(piece) = state.get_piece(Position::new(row, col + 1).unwrap()) {
-                        if state.get_en_passant_square() == Some(&Position::new(row, col + 1).unwrap())
-                        {
-                            if piece.get_player() != self.get_player() {
-                                if let Some(to_pos) =
-                                    Position::new((row as i8 + idx).try_into().unwrap(), col + 1)
-                                {
-                                    result.push(Move::new(self.get_position(), to_pos));
-                                }
-                            }
-                        }
-                    }
-            }
-
-                result
-            }
-            Self::Knight(SharedData {
-                position: _,
-                player: _,
-            }) => {
-                let mut possible_shifts = iproduct!([-2, 2], [-1, 1]).collect_vec();
-                possible_shifts.extend(iproduct!([-1, 1], [-2, 2]));
-                self.get_moves_shifts(possible_shifts, state)
-            }
-            Self::Bishop(_) => {
-                let directions = iproduct!([1, -1], [1, -1]).collect_vec();
-                self.get_moves_lines(directions, state)
-            }
-            Self::Rook(_, _) => {
-                let directions = vec![(1, 0), (0, 1), (-1, 0), (0, -1)];
-                self.get_moves_lines(directions, state)
-            }
-            Self::Queen(_) => {
-                let directions = iproduct!([1, 0, -1], [1, 0, -1])
-                    .filter(|&x| x != (0, 0))
-                    .collect_vec();
-                self.get_moves_lines(directions, state)
-            }
-            Self::King(_, can_castle) => {
-                let shifts = iproduct!([1, 0, -1], [1, 0, -1])
-                    .filter(|&x| x != (0, 0))
-                    .collect_vec();
-                let mut result = self.get_moves_shifts(shifts, state);
-                let (row, _) = self.get_position().as_tuple();
-                // TODO: add rules for when enemy pieces attack the squares
-                // between the king and the rook.
-
-                if can_castle {
-                    if let Some(chess_move) = self.check_castle_move(state, false) {
-                        result.push(chess_move);
-                    }
-                    if let Some(chess_move) = self.check_castle_move(state, true) {
-                        result.push(chess_move);
-                    }
-                }
-                result
-            }
-        }
+        state.get_pseudo_legal_moves(self.get_position())
     }
 }
 
@@ -1,7 +1,7 @@
 use wasm_bindgen::prelude::*;
 #[wasm_bindgen]
 #[repr(u8)]
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Player {
     Black,
     White,
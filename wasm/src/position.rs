@@ -1,5 +1,7 @@
+use std::{fmt, str::FromStr};
+
 /// to rows (1..=8) and columns (A-H).
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Position {
     row: u8,
     column: u8,
@@ -9,7 +11,7 @@ impl Position {
     /// Creates a new position if both the row and the column are within a
     /// range 0..=7, otherwise returns no value.
     pub fn new(row: u8, column: u8) -> Option<Position> {
-        let range = 0..=7 as u8;
+        let range = 0..=7_u8;
         if range.contains(&row) && range.contains(&column) {
             Some(Self { row, column })
         } else {
@@ -28,9 +30,37 @@ impl Position {
     pub fn get_column(&self) -> u8 {
         self.column
     }
+    /// Returns a position shifted by a given number of rows and columns, or no
+    /// value if the result would fall outside of the board.
+    pub fn shifted(&self, row_shift: i8, column_shift: i8) -> Option<Position> {
+        let row = u8::try_from(self.row as i8 + row_shift).ok()?;
+        let column = u8::try_from(self.column as i8 + column_shift).ok()?;
+        Position::new(row, column)
+    }
     /// Returns an array for easier handling and conversion in JS. The array
     /// consists of two u8 values of form: [row, column];
     pub fn as_js_tuple(&self) -> Box<[u8]> {
         Box::new([self.row, self.column])
     }
 }
+
+impl fmt::Display for Position {
+    /// Formats the position as a square name, e.g. "e4".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", (b'a' + self.column) as char, self.row + 1)
+    }
+}
+
+impl FromStr for Position {
+    type Err = String;
+
+    /// Parses a square name, e.g. "e4", into a position.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.as_bytes() {
+            [column @ b'a'..=b'h', row @ b'1'..=b'8'] => {
+                Ok(Position::new(row - b'1', column - b'a').unwrap())
+            }
+            _ => Err(format!("Invalid square name: '{}'", name)),
+        }
+    }
+}
@@ -0,0 +1,587 @@
+use crate::evaluation::{self, EvalWeights};
+use crate::gamestate::{GameState, PositionKey};
+use crate::piece::PieceType;
+use crate::player::Player;
+use crate::transposition::{Bound, TranspositionTable};
+use crate::{bitboard, utils, Move, PromotionType};
+use itertools::Itertools;
+use wasm_bindgen::prelude::*;
+
+/// The score of a position in which the player to move has been checkmated.
+/// A mate found further in the search is scored closer to zero by the number
+/// of plies leading to it, so shorter mates are preferred.
+pub const MATE_SCORE: i32 = 30_000;
+/// The bound on all the scores, used as the initial search window.
+const INFINITY: i32 = 32_000;
+/// The deepest a search can ever go, including the quiescence search.
+const MAX_PLY: usize = 64;
+/// Scores with a larger absolute value mean a forced mate.
+pub const MATE_THRESHOLD: i32 = MATE_SCORE - MAX_PLY as i32;
+/// The deepest iteration started by a search limited only by time.
+const MAX_DEPTH: u32 = 32;
+/// How many nodes are searched between two checks of the time limit.
+const TIME_CHECK_INTERVAL: u64 = 1024;
+
+/// The values of the pieces used to order the captures, indexed by their
+/// type.
+const PIECE_VALUES: [i32; 6] = [100, 320, 330, 500, 900, 0];
+
+/// The move ordering bonuses; captures and promotions are searched first,
+/// then the killer moves, then the other moves by their history.
+const CAPTURE_BONUS: i32 = 1_000_000;
+const KILLER_BONUS: i32 = 900_000;
+
+/// The condition for stopping a search: a depth reached by the iterative
+/// deepening, a number of milliseconds or a number of searched nodes,
+/// whichever comes first.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchLimit {
+    depth: Option<u32>,
+    time: Option<u64>,
+    nodes: Option<u64>,
+}
+
+#[wasm_bindgen]
+impl SearchLimit {
+    /// Searches until a given depth in plies is completed.
+    pub fn depth(depth: u32) -> Self {
+        Self {
+            depth: Some(depth.max(1)),
+            time: None,
+            nodes: None,
+        }
+    }
+    /// Searches for a given number of milliseconds; the first iteration is
+    /// always completed, so there is a move to return.
+    pub fn time(time: u32) -> Self {
+        Self {
+            depth: None,
+            time: Some(time.into()),
+            nodes: None,
+        }
+    }
+    /// Searches until a given depth is completed, but no longer than a given
+    /// number of milliseconds.
+    pub fn depth_and_time(depth: u32, time: u32) -> Self {
+        Self {
+            time: Some(time.into()),
+            ..Self::depth(depth)
+        }
+    }
+    /// Searches until a given depth is completed, but no more than a given
+    /// number of nodes; unlike a time limit, this gives the same result on
+    /// every machine.
+    pub fn depth_and_nodes(depth: u32, nodes: u32) -> Self {
+        Self {
+            nodes: Some(nodes.into()),
+            ..Self::depth(depth)
+        }
+    }
+}
+
+/// The outcome of a search: the best move found, if there is any legal move,
+/// and its score in centipawns from the perspective of the player to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    /// The depth of the last completed iteration.
+    pub depth: u32,
+    pub nodes: u64,
+}
+
+impl SearchResult {
+    /// Returns the number of moves until a forced mate, positive if the
+    /// player to move gives it and negative if they get mated.
+    pub fn get_mate(&self) -> Option<i32> {
+        if self.score.abs() <= MATE_THRESHOLD {
+            return None;
+        }
+        let moves = (MATE_SCORE - self.score.abs() + 1) / 2;
+        Some(if self.score > 0 { moves } else { -moves })
+    }
+}
+
+/// A negamax alpha-beta search with iterative deepening and a quiescence
+/// search of captures at the leaves. The searched positions are stored in a
+/// transposition table, which can be kept between searches.
+pub struct Search<'a> {
+    limit: SearchLimit,
+    table: &'a mut TranspositionTable,
+    weights: EvalWeights,
+    start_time: f64,
+    nodes: u64,
+    /// The depth of the last completed iteration.
+    depth: u32,
+    is_stopped: bool,
+    /// The keys of the positions preceding the searched one, both from the
+    /// game and from the current line of the search.
+    history_keys: Vec<PositionKey>,
+    /// Two quiet moves per ply that have recently caused a beta cutoff.
+    killers: [[Option<Move>; 2]; MAX_PLY],
+    /// The bonus of quiet moves, indexed by the player and the squares of
+    /// the move, growing with every beta cutoff they cause.
+    history: Vec<[[i32; 64]; 64]>,
+    /// The best move of the previous iteration, searched first at the root.
+    root_move: Option<Move>,
+    root_best: Option<Move>,
+}
+
+impl<'a> Search<'a> {
+    /// Creates a search with a given limit; the keys of the positions
+    /// preceding the searched one in the game allow to detect repetitions.
+    pub fn new(
+        limit: SearchLimit,
+        history_keys: Vec<PositionKey>,
+        table: &'a mut TranspositionTable,
+    ) -> Self {
+        table.new_search();
+        Self {
+            limit,
+            table,
+            weights: EvalWeights::default(),
+            start_time: 0.0,
+            nodes: 0,
+            depth: 0,
+            is_stopped: false,
+            history_keys,
+            killers: [[None; 2]; MAX_PLY],
+            history: vec![[[0; 64]; 64]; 2],
+            root_move: None,
+            root_best: None,
+        }
+    }
+
+    /// Replaces the default weights of the evaluation used by the search.
+    pub fn with_weights(self, weights: EvalWeights) -> Self {
+        Self { weights, ..self }
+    }
+
+    /// Searches a given position within the limit and returns the best move
+    /// found by the last completed iteration.
+    pub fn run(&mut self, state: &GameState) -> SearchResult {
+        self.start_time = utils::now();
+        let mut state = state.clone();
+        let mut result = SearchResult {
+            best_move: None,
+            score: 0,
+            depth: 0,
+            nodes: 0,
+        };
+        let max_depth = self.limit.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH);
+        for depth in 1..=max_depth {
+            self.root_best = None;
+            let score = self.negamax(&mut state, depth, 0, -INFINITY, INFINITY);
+            if self.is_stopped {
+                break;
+            }
+            self.root_move = self.root_best;
+            self.depth = depth;
+            result = SearchResult {
+                best_move: self.root_best,
+                score,
+                depth,
+                nodes: self.nodes,
+            };
+            // There is no point in searching deeper once a mate is found.
+            if self.root_best.is_none() || score.abs() > MATE_THRESHOLD {
+                break;
+            }
+        }
+        result.nodes = self.nodes;
+        result
+    }
+
+    /// Returns the number of nodes searched so far.
+    pub fn get_nodes(&self) -> u64 {
+        self.nodes
+    }
+    /// Returns the depth of the last completed iteration.
+    pub fn get_depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Searches every legal move of a given position within the limit and
+    /// returns their scores from the last completed iteration, the best
+    /// first. Unlike `run`, this gives exact scores of the worse moves too.
+    pub fn score_moves(&mut self, state: &GameState) -> Vec<(Move, i32)> {
+        self.start_time = utils::now();
+        let mut state = state.clone();
+        let key = state.get_position_key();
+        let mut scored_moves = state
+            .get_all_moves()
+            .into_iter()
+            .map(|r#move| (r#move, 0))
+            .collect_vec();
+        let max_depth = self.limit.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH);
+        for depth in 1..=max_depth {
+            let mut iteration = Vec::with_capacity(scored_moves.len());
+            for &(r#move, _) in &scored_moves {
+                let undo = state.make_move(r#move);
+                self.history_keys.push(key);
+                let score = -self.negamax(&mut state, depth - 1, 1, -INFINITY, INFINITY);
+                self.history_keys.pop();
+                state.unmake_move(undo);
+                if self.is_stopped {
+                    break;
+                }
+                iteration.push((r#move, score));
+            }
+            if self.is_stopped {
+                break;
+            }
+            iteration.sort_by_key(|&(_, score)| -score);
+            scored_moves = iteration;
+            self.depth = depth;
+            self.root_move = scored_moves.first().map(|&(r#move, _)| r#move);
+            if self.root_move.is_none() {
+                break;
+            }
+        }
+        scored_moves
+    }
+
+    /// Returns the score of a position searched to a given depth, with the
+    /// scores outside of the window between alpha and beta being bounds.
+    fn negamax(
+        &mut self,
+        state: &mut GameState,
+        depth: u32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        if ply > 0 && self.is_draw(state) {
+            return 0;
+        }
+        let is_check = state.is_checked();
+        // Positions in check are searched a ply deeper, so the search doesn't
+        // stop right before the escape turns out to be impossible.
+        let depth = if is_check { depth + 1 } else { depth };
+        if depth == 0 || ply >= MAX_PLY - 1 {
+            return self.quiescence(state, ply, alpha, beta);
+        }
+        self.count_node();
+        if self.is_stopped {
+            return 0;
+        }
+
+        let key = state.get_position_key();
+        let entry = self.table.probe(key);
+        if let Some(entry) = entry.filter(|entry| ply > 0 && u32::from(entry.depth) >= depth) {
+            let score = entry.get_score(ply);
+            let is_usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if is_usable {
+                return score;
+            }
+        }
+        let table_move = entry.and_then(|entry| entry.best_move);
+
+        let player = state.get_current_player();
+        let mut moves = state.get_all_pseudo_legal_moves();
+        let priority_move = if ply == 0 {
+            self.root_move.or(table_move)
+        } else {
+            table_move
+        };
+        self.order_moves(state, &mut moves, ply, priority_move);
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        let mut has_legal_move = false;
+        for r#move in moves {
+            let undo = state.make_move(r#move);
+            if state.is_player_checked(player) {
+                state.unmake_move(undo);
+                continue;
+            }
+            has_legal_move = true;
+            self.history_keys.push(key);
+            let score = -self.negamax(state, depth - 1, ply + 1, -beta, -alpha);
+            self.history_keys.pop();
+            state.unmake_move(undo);
+            if self.is_stopped {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(r#move);
+                if ply == 0 {
+                    self.root_best = Some(r#move);
+                }
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                if !r#move.is_capture() && r#move.get_promotion().is_none() {
+                    self.store_killer(r#move, ply);
+                    let (from, to) = squares_of(r#move);
+                    self.history[player as usize][from][to] += (depth * depth) as i32;
+                }
+                break;
+            }
+        }
+        if !has_legal_move {
+            best_score = if is_check {
+                -MATE_SCORE + ply as i32
+            } else {
+                0
+            };
+        }
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha || !has_legal_move {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.table
+            .store(key, depth, bound, best_score, best_move, ply);
+        best_score
+    }
+
+    /// Returns the score of a position once the captures and promotions have
+    /// been played out, so the evaluation isn't taken in the middle of an
+    /// exchange. The player to move may also stop capturing.
+    fn quiescence(&mut self, state: &mut GameState, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.count_node();
+        if self.is_stopped {
+            return 0;
+        }
+        let stand_pat = self.evaluate(state);
+        if stand_pat >= beta || ply >= MAX_PLY - 1 {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let player = state.get_current_player();
+        let mut moves = state.get_all_pseudo_legal_moves();
+        moves.retain(|r#move| r#move.is_capture() || r#move.get_promotion().is_some());
+        self.order_moves(state, &mut moves, ply, None);
+        let mut best_score = stand_pat;
+        for r#move in moves {
+            let undo = state.make_move(r#move);
+            if state.is_player_checked(player) {
+                state.unmake_move(undo);
+                continue;
+            }
+            let score = -self.quiescence(state, ply + 1, -beta, -alpha);
+            state.unmake_move(undo);
+            if self.is_stopped {
+                return 0;
+            }
+            best_score = best_score.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best_score
+    }
+
+    /// Returns the static evaluation of a position from the perspective of
+    /// the player to move.
+    fn evaluate(&self, state: &GameState) -> i32 {
+        let score = evaluation::evaluate(state, &self.weights);
+        match state.get_current_player() {
+            Player::White => score,
+            Player::Black => -score,
+        }
+    }
+
+    /// Returns true if a position reached in the search is drawn: it repeats
+    /// an earlier one, the fifty-move rule applies, or there is not enough
+    /// material to mate.
+    fn is_draw(&self, state: &GameState) -> bool {
+        let key = state.get_position_key();
+        // A capture or a pawn move makes any repetition across it impossible.
+        state.get_halfmove_clock() >= 100
+            || state.has_insufficient_material()
+            || self
+                .history_keys
+                .iter()
+                .rev()
+                .take(state.get_halfmove_clock() as usize)
+                .any(|&other| other == key)
+    }
+
+    /// Sorts the moves so the most promising ones are searched first, which
+    /// makes the alpha-beta cutoffs happen earlier.
+    fn order_moves(
+        &self,
+        state: &GameState,
+        moves: &mut [Move],
+        ply: usize,
+        priority_move: Option<Move>,
+    ) {
+        let player = state.get_current_player() as usize;
+        moves.sort_by_cached_key(|&r#move| {
+            let score = if Some(r#move) == priority_move {
+                i32::MAX
+            } else if r#move.is_capture() || r#move.get_promotion().is_some() {
+                CAPTURE_BONUS + mvv_lva(state, r#move)
+            } else if let Some(idx) = self.killers[ply].iter().position(|&k| k == Some(r#move)) {
+                KILLER_BONUS - idx as i32
+            } else {
+                let (from, to) = squares_of(r#move);
+                self.history[player][from][to]
+            };
+            -score
+        });
+    }
+
+    /// Remembers a quiet move that has caused a beta cutoff at a given ply,
+    /// pushing out the older of the two killer moves.
+    fn store_killer(&mut self, r#move: Move, ply: usize) {
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(r#move) {
+            killers[1] = killers[0];
+            killers[0] = Some(r#move);
+        }
+    }
+
+    /// Counts a searched node and stops the search if it's out of time or
+    /// nodes. The first iteration is never stopped.
+    fn count_node(&mut self) {
+        self.nodes += 1;
+        if self.root_move.is_none() {
+            return;
+        }
+        if self.limit.nodes.is_some_and(|nodes| self.nodes >= nodes) {
+            self.is_stopped = true;
+        }
+        if let Some(time) = self.limit.time {
+            if self.nodes.is_multiple_of(TIME_CHECK_INTERVAL)
+                && utils::now() - self.start_time >= time as f64
+            {
+                self.is_stopped = true;
+            }
+        }
+    }
+}
+
+/// Returns the indices of the start and end squares of a move.
+fn squares_of(r#move: Move) -> (usize, usize) {
+    (
+        bitboard::square(r#move.get_current_position()),
+        bitboard::square(r#move.get_end_position()),
+    )
+}
+
+/// Returns the ordering score of a capture or a promotion: the most valuable
+/// victim first, and among captures of the same piece the least valuable
+/// attacker first (MVV-LVA).
+fn mvv_lva(state: &GameState, r#move: Move) -> i32 {
+    let victim = if r#move.is_en_passant() {
+        Some(PieceType::Pawn)
+    } else {
+        state.get_piece_type(r#move.get_end_position())
+    };
+    let attacker = state
+        .get_piece_type(r#move.get_current_position())
+        .unwrap_or(PieceType::Pawn);
+    let promotion = r#move
+        .get_promotion()
+        .map_or(0, |promotion| match promotion {
+            PromotionType::Queen => PIECE_VALUES[PieceType::Queen as usize],
+            _ => 0,
+        });
+    let victim_value = victim.map_or(0, |victim| PIECE_VALUES[victim as usize]);
+    10 * (victim_value + promotion) - attacker as i32
+}
+
+/// Returns the best move in a given position and its score, searched within
+/// a given limit.
+pub fn find_best_move(
+    state: &GameState,
+    limit: SearchLimit,
+    history_keys: Vec<PositionKey>,
+    table: &mut TranspositionTable,
+) -> SearchResult {
+    Search::new(limit, history_keys, table).run(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(fen: &str, limit: SearchLimit) -> SearchResult {
+        let mut table = TranspositionTable::new(1).unwrap();
+        find_best_move(
+            &GameState::from_fen(fen).unwrap(),
+            limit,
+            vec![],
+            &mut table,
+        )
+    }
+
+    #[test]
+    fn test_finds_mate() {
+        let result = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", SearchLimit::depth(3));
+        assert_eq!(result.best_move.unwrap().to_uci(), "a1a8");
+        assert_eq!(result.score, MATE_SCORE - 1);
+        assert_eq!(result.get_mate(), Some(1));
+
+        let result = search(
+            "2r3k1/5ppp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1",
+            SearchLimit::depth(4),
+        );
+        assert_eq!(result.best_move.unwrap().to_uci(), "d2d8");
+        assert_eq!(result.get_mate(), Some(2));
+    }
+
+    #[test]
+    fn test_mated_and_stalemated() {
+        let result = search("7k/8/8/8/8/8/5PPP/r5K1 w - - 0 1", SearchLimit::depth(2));
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, -MATE_SCORE);
+        let result = search("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", SearchLimit::depth(2));
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, 0);
+    }
+
+    #[test]
+    fn test_wins_material() {
+        let result = search("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1", SearchLimit::depth(2));
+        assert_eq!(result.best_move.unwrap().to_uci(), "d1d5");
+        assert!(result.score > 400);
+    }
+
+    #[test]
+    fn test_quiescence_sees_recapture() {
+        // Taking the pawn loses the queen to the rook, which only the
+        // quiescence search notices at depth 1.
+        let result = search("4k3/4r3/8/8/8/4p3/8/4QK2 w - - 0 1", SearchLimit::depth(1));
+        assert_ne!(result.best_move.unwrap().to_uci(), "e1e3");
+    }
+
+    #[test]
+    fn test_repetition_is_draw() {
+        // White is a queen down, so repeating a position from the game is the
+        // best they can do.
+        let state = GameState::from_fen("k7/8/8/4q3/8/8/8/7K w - - 10 20").unwrap();
+        let repeated = GameState::transform_state(&state, Move::from_uci("h1g1").unwrap());
+        let result = find_best_move(
+            &state,
+            SearchLimit::depth(2),
+            vec![repeated.get_position_key(), state.get_position_key()],
+            &mut TranspositionTable::new(1).unwrap(),
+        );
+        assert_eq!(result.best_move.unwrap().to_uci(), "h1g1");
+        assert_eq!(result.score, 0);
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let result = search(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            SearchLimit::time(100),
+        );
+        assert!(result.best_move.is_some());
+        assert!(result.depth >= 1);
+    }
+}
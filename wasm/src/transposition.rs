@@ -0,0 +1,232 @@
+use crate::gamestate::PositionKey;
+use crate::search::MATE_THRESHOLD;
+use crate::Move;
+
+/// The default size of a transposition table in megabytes.
+pub const DEFAULT_SIZE: u32 = 16;
+/// The largest allowed size in megabytes, well below the 4 GB address space
+/// of WebAssembly.
+pub const MAX_SIZE: u32 = 512;
+
+/// The relation between the score stored for a position and its real score,
+/// which follows from the search window the position was searched with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// The score is exact.
+    Exact,
+    /// The real score is at least the stored one (a beta cutoff).
+    Lower,
+    /// The real score is at most the stored one (no move raised alpha).
+    Upper,
+}
+
+/// The result of searching a position, stored to be reused when the search
+/// reaches it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    key: PositionKey,
+    pub best_move: Option<Move>,
+    score: i32,
+    pub depth: u8,
+    pub bound: Bound,
+    /// The search the entry was stored in, so the older entries are replaced
+    /// first.
+    generation: u8,
+}
+
+impl Entry {
+    /// Returns the stored score as seen from a position a given number of
+    /// plies from the root of the search.
+    pub fn get_score(&self, ply: usize) -> i32 {
+        score_from_table(self.score, ply)
+    }
+}
+
+/// Converts a score to be stored in the table. Mate scores count the plies
+/// from the root of the search, while the same position can be reached at
+/// different plies, so they are stored as counted from the position itself.
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if score > MATE_THRESHOLD {
+        score + ply as i32
+    } else if score < -MATE_THRESHOLD {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+/// Converts a stored score back to the one counted from the root of the
+/// search; see `score_to_table`.
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if score > MATE_THRESHOLD {
+        score - ply as i32
+    } else if score < -MATE_THRESHOLD {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+/// Returns Err(String) if a given size of a table in megabytes is out of
+/// range.
+pub fn check_size(megabytes: u32) -> Result<(), String> {
+    if !(1..=MAX_SIZE).contains(&megabytes) {
+        return Err(format!(
+            "The size of the transposition table has to be between 1 and {} MB",
+            MAX_SIZE
+        ));
+    }
+    Ok(())
+}
+
+/// A hash table of searched positions with a fixed number of slots, indexed
+/// by the Zobrist hash of the positions. Every slot holds a single entry, so
+/// storing a position may push out another one.
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+    generation: u8,
+}
+
+impl TranspositionTable {
+    /// Creates an empty table taking up at most a given number of megabytes;
+    /// returns Err(String) if the size is out of range or the memory can't be
+    /// allocated.
+    pub fn new(megabytes: u32) -> Result<Self, String> {
+        check_size(megabytes)?;
+        let bytes = megabytes as usize * 1024 * 1024;
+        // The number of slots is a power of two, so the index is just the
+        // lowest bits of the hash.
+        let len = 1 << (bytes / std::mem::size_of::<Option<Entry>>()).ilog2();
+        let mut entries = Vec::new();
+        entries
+            .try_reserve_exact(len)
+            .map_err(|_| "Could not allocate the transposition table".to_string())?;
+        entries.resize(len, None);
+        Ok(Self {
+            entries,
+            generation: 0,
+        })
+    }
+    /// Removes all the entries.
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+        self.generation = 0;
+    }
+    /// Marks the start of a new search; the entries stored by the previous
+    /// ones are kept, but get replaced first.
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+    /// Returns the entry stored for a position, if there is one.
+    pub fn probe(&self, key: PositionKey) -> Option<Entry> {
+        self.entries[self.get_index(key)].filter(|entry| entry.key == key)
+    }
+    /// Stores the result of searching a position a given number of plies
+    /// from the root. It replaces the entry in its slot unless that one
+    /// holds another position searched deeper in the current search.
+    pub fn store(
+        &mut self,
+        key: PositionKey,
+        depth: u32,
+        bound: Bound,
+        score: i32,
+        best_move: Option<Move>,
+        ply: usize,
+    ) {
+        let depth = depth.min(u8::MAX.into()) as u8;
+        let generation = self.generation;
+        let index = self.get_index(key);
+        let slot = &mut self.entries[index];
+        if let Some(entry) = slot {
+            if entry.key != key && entry.generation == generation && entry.depth > depth {
+                return;
+            }
+        }
+        // A search without a best move, e.g. one failing low, shouldn't
+        // forget the move found for the position earlier.
+        let best_move = best_move.or_else(|| {
+            slot.filter(|entry| entry.key == key)
+                .and_then(|entry| entry.best_move)
+        });
+        *slot = Some(Entry {
+            key,
+            best_move,
+            score: score_to_table(score, ply),
+            depth,
+            bound,
+            generation,
+        });
+    }
+    /// Returns the number of slots in the table.
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+    fn get_index(&self, key: PositionKey) -> usize {
+        key as usize & (self.entries.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::MATE_SCORE;
+
+    #[test]
+    fn test_size() {
+        let table = TranspositionTable::new(1).unwrap();
+        assert!(table.capacity().is_power_of_two());
+        assert!(table.capacity() * std::mem::size_of::<Option<Entry>>() <= 1024 * 1024);
+        assert!(TranspositionTable::new(0).is_err());
+        assert!(TranspositionTable::new(MAX_SIZE + 1).is_err());
+    }
+
+    #[test]
+    fn test_store_and_probe() {
+        let mut table = TranspositionTable::new(1).unwrap();
+        let best_move = Move::from_uci("e2e4").unwrap();
+        table.store(42, 5, Bound::Lower, 30, Some(best_move), 3);
+        let entry = table.probe(42).unwrap();
+        assert_eq!(entry.best_move, Some(best_move));
+        assert_eq!(
+            (entry.depth, entry.bound, entry.get_score(0)),
+            (5, Bound::Lower, 30)
+        );
+        // Another position falling into the same slot doesn't match.
+        assert_eq!(table.probe(42 + table.capacity() as u64), None);
+        table.clear();
+        assert_eq!(table.probe(42), None);
+    }
+
+    #[test]
+    fn test_mate_scores() {
+        let mut table = TranspositionTable::new(1).unwrap();
+        // A mate in 3 plies from a position reached at ply 4 is a mate in 7
+        // plies from the root; reached at ply 2, it's a mate in 5.
+        table.store(7, 3, Bound::Exact, MATE_SCORE - 7, None, 4);
+        assert_eq!(table.probe(7).unwrap().get_score(2), MATE_SCORE - 5);
+        table.store(7, 3, Bound::Exact, -MATE_SCORE + 7, None, 4);
+        assert_eq!(table.probe(7).unwrap().get_score(2), -MATE_SCORE + 5);
+    }
+
+    #[test]
+    fn test_replacement() {
+        let mut table = TranspositionTable::new(1).unwrap();
+        let other_key = 1 + table.capacity() as u64;
+        table.store(1, 6, Bound::Exact, 0, None, 0);
+        // A shallower entry for another position doesn't replace a deeper
+        // one from the same search...
+        table.store(other_key, 2, Bound::Exact, 0, None, 0);
+        assert!(table.probe(1).is_some());
+        assert!(table.probe(other_key).is_none());
+        // ...but it does replace one from an earlier search.
+        table.new_search();
+        table.store(other_key, 2, Bound::Exact, 0, None, 0);
+        assert!(table.probe(1).is_none());
+        assert!(table.probe(other_key).is_some());
+        // The best move is kept if the new entry has none.
+        let best_move = Move::from_uci("g1f3").unwrap();
+        table.store(other_key, 3, Bound::Lower, 10, Some(best_move), 0);
+        table.store(other_key, 4, Bound::Upper, -10, None, 0);
+        assert_eq!(table.probe(other_key).unwrap().best_move, Some(best_move));
+    }
+}
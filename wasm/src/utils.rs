@@ -8,3 +8,27 @@ cfg_if! {
         pub fn set_panic_hook() {}
     }
 }
+
+cfg_if! {
+    if #[cfg(target_arch = "wasm32")] {
+        use wasm_bindgen::prelude::*;
+
+        #[wasm_bindgen]
+        extern "C" {
+            #[wasm_bindgen(js_namespace = Date, js_name = now)]
+            fn date_now() -> f64;
+        }
+
+        /// Returns the current time in milliseconds.
+        pub fn now() -> f64 {
+            date_now()
+        }
+    } else {
+        /// Returns the current time in milliseconds.
+        pub fn now() -> f64 {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0.0, |duration| duration.as_secs_f64() * 1000.0)
+        }
+    }
+}
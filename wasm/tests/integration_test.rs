@@ -12,3 +12,599 @@
 //     let result: Vec<Game::ChessMove> = game.make_move((4, 4));
 //     assert_eq!(result.len(), 14);
 // }
+
+use chess_wasm::clock::TimeControl;
+use chess_wasm::difficulty::Difficulty;
+use chess_wasm::player::Player;
+use chess_wasm::search::SearchLimit;
+use chess_wasm::{Game, GameResult, GameStatus, PromotionType};
+
+#[test]
+fn test_fools_mate() {
+    let mut game = Game::new();
+    game.make_move(1, 5, 2, 5, None, None).unwrap();
+    game.make_move(6, 4, 4, 4, None, None).unwrap();
+    game.make_move(1, 6, 3, 6, None, None).unwrap();
+    assert_eq!(game.get_status(), GameStatus::Ongoing);
+    game.make_move(7, 3, 3, 7, None, None).unwrap();
+    assert!(game.is_finished());
+    assert!(game.is_check());
+    assert_eq!(game.get_status(), GameStatus::Checkmate);
+    assert_eq!(game.get_winner(), Some(Player::Black));
+    assert!(game.make_move(1, 0, 2, 0, None, None).is_err());
+}
+
+fn shuffle_knights(game: &mut Game) {
+    game.make_move(0, 6, 2, 5, None, None).unwrap();
+    game.make_move(7, 6, 5, 5, None, None).unwrap();
+    game.make_move(2, 5, 0, 6, None, None).unwrap();
+    game.make_move(5, 5, 7, 6, None, None).unwrap();
+}
+
+#[test]
+fn test_threefold_repetition() {
+    let mut game = Game::new();
+    shuffle_knights(&mut game);
+    assert!(!game.can_claim_draw());
+    assert!(game.claim_draw().is_err());
+    shuffle_knights(&mut game);
+    assert!(game.can_claim_draw());
+    assert_eq!(game.get_status(), GameStatus::Ongoing);
+    game.claim_draw().unwrap();
+    assert!(game.is_finished());
+    assert_eq!(game.get_status(), GameStatus::DrawByRepetition);
+    assert_eq!(game.get_winner(), None);
+}
+
+#[test]
+fn test_fivefold_repetition() {
+    let mut game = Game::new();
+    for _ in 0..3 {
+        shuffle_knights(&mut game);
+    }
+    assert_eq!(game.get_status(), GameStatus::Ongoing);
+    shuffle_knights(&mut game);
+    assert_eq!(game.get_status(), GameStatus::DrawByRepetition);
+    assert!(game.make_move(1, 4, 3, 4, None, None).is_err());
+}
+
+#[test]
+fn test_game_from_fen() {
+    let fen = "4k3/8/8/8/8/8/8/R3K3 w Q - 12 40";
+    let mut game = Game::from_fen(fen).unwrap();
+    assert_eq!(game.fen(), fen);
+    game.make_move(0, 4, 0, 2, None, None).unwrap();
+    assert_eq!(game.fen(), "4k3/8/8/8/8/8/8/2KR4 b - - 13 40");
+    assert!(Game::from_fen("not a fen").is_err());
+}
+
+#[test]
+fn test_game_insufficient_material() {
+    let mut game = Game::from_fen("4k3/8/8/8/8/8/3r4/4K3 w - - 0 1").unwrap();
+    assert_eq!(game.get_status(), GameStatus::Ongoing);
+    game.make_move(0, 4, 1, 3, None, None).unwrap();
+    assert_eq!(game.get_status(), GameStatus::DrawByInsufficientMaterial);
+}
+
+#[test]
+fn test_pgn_export() {
+    let mut game = Game::new();
+    game.set_tag("White", "Alice").unwrap();
+    game.set_tag("Black", "Bob").unwrap();
+    game.set_tag("Annotator", "Nobody").unwrap();
+    assert!(game.set_tag("Result", "1-0").is_err());
+    assert!(game.set_tag("Bad tag", "x").is_err());
+    game.make_move(1, 5, 2, 5, None, None).unwrap();
+    game.make_move(6, 4, 4, 4, None, None).unwrap();
+    game.make_move(1, 6, 3, 6, None, None).unwrap();
+    game.make_move(7, 3, 3, 7, None, None).unwrap();
+    assert_eq!(
+        game.pgn(),
+        "[Event \"?\"]\n\
+         [Site \"?\"]\n\
+         [Date \"????.??.??\"]\n\
+         [Round \"?\"]\n\
+         [White \"Alice\"]\n\
+         [Black \"Bob\"]\n\
+         [Result \"0-1\"]\n\
+         [Termination \"normal\"]\n\
+         [Annotator \"Nobody\"]\n\
+         \n\
+         1. f3 e5 2. g4 Qh4# 0-1\n"
+    );
+}
+
+#[test]
+fn test_pgn_export_from_fen() {
+    let fen = "4k3/8/8/8/8/8/8/R3K3 b Q - 0 30";
+    let mut game = Game::from_fen(fen).unwrap();
+    game.make_move(7, 4, 7, 3, None, None).unwrap();
+    game.make_move(0, 4, 0, 2, None, None).unwrap();
+    let pgn = game.pgn();
+    assert!(
+        pgn.contains("[Result \"*\"]\n[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/R3K3 b Q - 0 30\"]\n")
+    );
+    assert!(pgn.ends_with("\n\n30... Kd8 31. O-O-O+ *\n"));
+}
+
+#[test]
+fn test_pgn_import() {
+    let pgn = "[Event \"Opera Game\"]\n\
+               [Site \"Paris\"]\n\
+               [White \"Paul Morphy\"]\n\
+               [Black \"Duke Karl / Count Isouard\"]\n\
+               [Result \"1-0\"]\n\
+               \n\
+               1. e4 e5 2. Nf3 d6 3. d4 Bg4 {This is a weak move already.} 4. dxe5 Bxf3\n\
+               5. Qxf3 dxe5 6. Bc4 Nf6 7. Qb3 Qe7 8. Nc3 c6 9. Bg5 b5 10. Nxb5 cxb5\n\
+               11. Bxb5+ Nbd7 12. O-O-O Rd8 13. Rxd7 Rxd7 14. Rd1 Qe6 15. Bxd7+ Nxd7\n\
+               16. Qb8+ Nxb8 17. Rd8# 1-0\n";
+    let game = Game::from_pgn(pgn).unwrap();
+    assert_eq!(game.get_status(), GameStatus::Checkmate);
+    assert_eq!(game.get_winner(), Some(Player::White));
+    let exported = game.pgn();
+    assert!(exported.starts_with(
+        "[Event \"Opera Game\"]\n[Site \"Paris\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n"
+    ));
+    assert!(exported.ends_with(
+        "[Result \"1-0\"]\n[Termination \"normal\"]\n\n\
+         1. e4 e5 2. Nf3 d6 3. d4 Bg4 {This is a weak move already.} 4. dxe5 Bxf3 5.\n\
+         Qxf3 dxe5 6. Bc4 Nf6 7. Qb3 Qe7 8. Nc3 c6 9. Bg5 b5 10. Nxb5 cxb5 11. Bxb5+\n\
+         Nbd7 12. O-O-O Rd8 13. Rxd7 Rxd7 14. Rd1 Qe6 15. Bxd7+ Nxd7 16. Qb8+ Nxb8 17.\n\
+         Rd8# 1-0\n"
+    ));
+    assert_eq!(Game::from_pgn(&exported).unwrap().pgn(), exported);
+}
+
+#[test]
+fn test_pgn_import_errors() {
+    let pgn = "1. e4 e5 2. Nf3 Nf6 3. Bb5 Nf4 *";
+    assert_eq!(
+        Game::from_pgn(pgn).err(),
+        Some("Ply 6 (3... Nf4): No legal move matches 'Nf4'".to_string())
+    );
+    let pgn = "[FEN \"4k3/8/8/8/8/8/4K3/R6R w - - 0 1\"]\n\n1. Rd1 *";
+    assert_eq!(
+        Game::from_pgn(pgn).err(),
+        Some("Ply 1 (1. Rd1): Ambiguous move 'Rd1'".to_string())
+    );
+    assert!(Game::from_pgn("").is_err());
+}
+
+#[test]
+fn test_pgn_import_result_without_mate() {
+    let game = Game::from_pgn("1. e4 e5 2. Qh5 Nc6 1-0").unwrap();
+    assert_eq!(game.get_status(), GameStatus::Resignation);
+    assert_eq!(game.get_winner(), Some(Player::White));
+    let game = Game::from_pgn("1. d4 d5 1/2-1/2").unwrap();
+    assert_eq!(game.get_status(), GameStatus::DrawByAgreement);
+}
+
+#[test]
+fn test_san_moves() {
+    let mut game = Game::new();
+    assert_eq!(game.get_legal_moves_san().len(), 20);
+    assert!(game.get_legal_moves_san().contains(&"Nf3".to_string()));
+    game.make_move_san("e4", None).unwrap();
+    game.make_move_san("d5", None).unwrap();
+    game.make_move(3, 4, 4, 3, None, None).unwrap();
+    assert!(game.make_move_san("Qxd5", None).is_ok());
+    assert!(game.make_move_san("Qd5", None).is_err());
+    assert_eq!(game.get_san_moves(), ["e4", "d5", "exd5", "Qxd5"]);
+
+    let game = Game::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let legal = game.get_legal_moves_san();
+    for san in ["b8=Q+", "b8=R+", "b8=B", "b8=N", "Kd1", "Kf2"] {
+        assert!(legal.contains(&san.to_string()), "{}", san);
+    }
+    assert_eq!(legal.len(), 9);
+}
+
+#[test]
+fn test_uci_moves() {
+    let mut game = Game::new();
+    for uci in ["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "e1g1"] {
+        game.make_move_uci(uci, None).unwrap();
+    }
+    assert_eq!(
+        game.get_san_moves(),
+        ["e4", "e5", "Nf3", "Nc6", "Bc4", "Nf6", "O-O"]
+    );
+    assert_eq!(
+        game.make_move_uci("e5e4", None),
+        Err("Invalid move".to_string())
+    );
+    assert!(game.make_move_uci("e9e4", None).is_err());
+
+    let mut game = Game::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    game.make_move_uci("b7b8r", None).unwrap();
+    assert_eq!(game.fen(), "1R2k3/8/8/8/8/8/8/4K3 b - - 0 1");
+}
+
+#[test]
+fn test_promotion_must_be_chosen() {
+    let mut game = Game::from_fen("4k3/1P6/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+    assert_eq!(
+        game.make_move(6, 1, 7, 1, None, None),
+        Err("A promotion piece has to be chosen".to_string())
+    );
+    assert_eq!(
+        game.make_move(1, 4, 3, 4, Some(PromotionType::Queen), None),
+        Err("Only a pawn reaching the last rank can be promoted".to_string())
+    );
+    assert_eq!(game.get_moves(6, 1).len(), 1);
+    assert!(game.make_move_uci("b7b8", None).is_err());
+    game.make_move(6, 1, 7, 1, Some(PromotionType::Knight), None)
+        .unwrap();
+    assert_eq!(game.get_san_moves(), ["b8=N"]);
+}
+
+#[test]
+fn test_undo_redo() {
+    let mut game = Game::new();
+    assert!(game.undo().is_err());
+    game.make_move_san("e4", None).unwrap();
+    game.make_move_san("e5", None).unwrap();
+    game.make_move_san("Nf3", None).unwrap();
+    game.undo().unwrap();
+    game.undo().unwrap();
+    assert_eq!(game.get_ply(), 1);
+    assert_eq!(game.get_current_player(), Player::Black);
+    assert_eq!(
+        game.fen(),
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+    );
+    assert_eq!(game.get_san_moves(), ["e4"]);
+    game.redo().unwrap();
+    assert_eq!(game.get_san_moves(), ["e4", "e5"]);
+    assert_eq!(game.history().len(), 3);
+
+    game.make_move_san("d4", None).unwrap();
+    assert!(game.redo().is_err());
+    let history = game.history();
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[2].san, "d4");
+    assert_eq!(history[2].uci, "d2d4");
+    assert_eq!(
+        history[2].fen,
+        "rnbqkbnr/pppp1ppp/8/4p3/3PP3/8/PPP2PPP/RNBQKBNR b KQkq - 0 2"
+    );
+}
+
+#[test]
+fn test_goto_ply_in_finished_game() {
+    let mut game = Game::from_pgn("1. f3 e5 2. g4 Qh4# 0-1").unwrap();
+    game.goto_ply(0).unwrap();
+    assert_eq!(game.get_status(), GameStatus::Ongoing);
+    assert_eq!(game.fen(), Game::new().fen());
+    game.goto_ply(4).unwrap();
+    assert_eq!(game.get_status(), GameStatus::Checkmate);
+    assert!(game.goto_ply(5).is_err());
+
+    // A resignation applies only to the final position.
+    let mut game = Game::from_pgn("1. e4 e5 2. Qh5 Nc6 1-0").unwrap();
+    game.undo().unwrap();
+    assert_eq!(game.get_status(), GameStatus::Ongoing);
+    game.redo().unwrap();
+    assert_eq!(game.get_status(), GameStatus::Resignation);
+    game.undo().unwrap();
+    game.make_move_san("Nf6", None).unwrap();
+    assert_eq!(game.get_status(), GameStatus::Ongoing);
+
+    // Repetitions are counted only up to the current position.
+    let mut game = Game::new();
+    shuffle_knights(&mut game);
+    shuffle_knights(&mut game);
+    assert!(game.can_claim_draw());
+    game.undo().unwrap();
+    assert!(!game.can_claim_draw());
+}
+
+#[test]
+fn test_variations() {
+    let pgn = "[Event \"Lesson\"]\n\n\
+               1. e4 e5 (1... c5 {The Sicilian} 2. Nf3 (2. c3 d5) d6) 2. Nf3 Nc6 $1 *\n";
+    let mut game = Game::from_pgn(pgn).unwrap();
+    assert_eq!(game.get_san_moves(), ["e4", "e5", "Nf3", "Nc6"]);
+    assert_eq!(game.get_nags(), [1]);
+    assert!(game.pgn().ends_with(
+        "\n\n1. e4 e5 (1... c5 {The Sicilian} 2. Nf3 (2. c3 d5) 2... d6) 2. Nf3 Nc6 $1 *\n"
+    ));
+
+    game.goto_ply(1).unwrap();
+    assert_eq!(game.get_variations(), ["e5", "c5"]);
+    game.enter_variation(1).unwrap();
+    assert!(!game.is_in_main_line());
+    assert_eq!(game.get_comments(), ["The Sicilian"]);
+    game.enter_variation(1).unwrap();
+    assert_eq!(game.get_san_moves(), ["e4", "c5", "c3"]);
+    assert!(game.enter_variation(2).is_err());
+
+    game.promote_variation();
+    assert!(game.is_in_main_line());
+    game.add_comment("The Alapin");
+    game.add_nag(5);
+    assert!(game.pgn().ends_with(
+        "\n\n1. e4 c5 {The Sicilian} (1... e5 2. Nf3 Nc6 $1) 2. c3 $5 {The Alapin} (2. Nf3\n\
+         d6) 2... d5 *\n"
+    ));
+    let reread = Game::from_pgn(&game.pgn()).unwrap();
+    assert_eq!(reread.pgn(), game.pgn());
+
+    game.delete_branch().unwrap();
+    assert_eq!(game.get_san_moves(), ["e4", "c5"]);
+    assert_eq!(game.get_variations(), ["Nf3"]);
+    game.goto_ply(0).unwrap();
+    assert!(game.delete_branch().is_err());
+
+    // A variation can be added to the main line on purpose, and the moves
+    // made inside it stay there.
+    game.add_variation("d4").unwrap();
+    assert!(!game.is_in_main_line());
+    game.make_move_san("d5", None).unwrap();
+    assert!(!game.is_in_main_line());
+    game.goto_ply(0).unwrap();
+    assert_eq!(game.get_variations(), ["e4", "d4"]);
+    assert!(game.add_variation("e5").is_err());
+}
+
+#[test]
+fn test_new_move_after_undo_replaces_undone_moves() {
+    let mut game = Game::new();
+    for san in ["f3", "e5", "a3"] {
+        game.make_move_san(san, None).unwrap();
+    }
+    game.undo().unwrap();
+    game.make_move_san("g4", None).unwrap();
+    game.make_move_san("Qh4#", None).unwrap();
+    assert_eq!(game.get_result(), GameResult::Checkmate(Player::Black));
+    assert!(game.pgn().ends_with("\n\n1. f3 e5 2. g4 Qh4# 0-1\n"));
+    let history = game.history();
+    assert_eq!(
+        history
+            .iter()
+            .map(|entry| entry.san.as_str())
+            .collect::<Vec<_>>(),
+        ["f3", "e5", "g4", "Qh4#"]
+    );
+    game.goto_ply(2).unwrap();
+    assert_eq!(game.get_variations(), ["g4"]);
+}
+
+#[test]
+fn test_clock() {
+    let mut game = Game::new();
+    assert_eq!(game.get_remaining_time(Player::White), None);
+    assert!(game.start_clock(0.0).is_err());
+    game.set_time_control(TimeControl::fischer(60_000, 1000))
+        .unwrap();
+    game.start_clock(1000.0).unwrap();
+    assert!(game
+        .set_time_control(TimeControl::sudden_death(60_000))
+        .is_err());
+
+    // The move is timed when it's made, without updating the clock first.
+    game.make_move_san("e4", Some(6000.0)).unwrap();
+    assert_eq!(game.get_remaining_time(Player::White), Some(56_000.0));
+    game.update_clock(36_000.0);
+    assert_eq!(game.get_remaining_time(Player::Black), Some(30_000.0));
+    assert!(!game.is_finished());
+
+    game.update_clock(66_000.0);
+    assert_eq!(game.get_status(), GameStatus::Timeout);
+    assert_eq!(game.get_winner(), Some(Player::White));
+    assert!(game.make_move_san("e5", None).is_err());
+    assert_eq!(game.get_remaining_time(Player::Black), Some(0.0));
+    assert!(game.pgn().ends_with("1. e4 1-0\n"));
+}
+
+#[test]
+fn test_flag_fall_after_going_back() {
+    let mut game = Game::new();
+    game.set_time_control(TimeControl::sudden_death(10_000))
+        .unwrap();
+    game.start_clock(0.0).unwrap();
+    for (san, timestamp) in [("e4", 1000.0), ("e5", 2000.0), ("Nf3", 3000.0)] {
+        game.make_move_san(san, Some(timestamp)).unwrap();
+    }
+    // Black's time runs out while an earlier position is looked at.
+    game.goto_ply(1).unwrap();
+    game.update_clock(20_000.0);
+    assert_eq!(game.get_status(), GameStatus::Ongoing);
+    assert_eq!(game.history().len(), 3);
+    game.goto_ply(3).unwrap();
+    assert_eq!(game.get_status(), GameStatus::Timeout);
+    assert_eq!(game.get_winner(), Some(Player::White));
+    assert!(game.pgn().ends_with("1. e4 e5 2. Nf3 1-0\n"));
+}
+
+#[test]
+fn test_draw_by_timeout() {
+    // A lone knight can still checkmate with the help of Black's pawn, so
+    // Black's flag fall loses.
+    let mut game = Game::from_fen("4k3/4p3/8/8/8/8/8/4K1N1 b - - 0 1").unwrap();
+    game.set_time_control(TimeControl::sudden_death(1000))
+        .unwrap();
+    game.start_clock(0.0).unwrap();
+    game.update_clock(1000.0);
+    assert_eq!(game.get_status(), GameStatus::Timeout);
+    assert_eq!(game.get_winner(), Some(Player::White));
+
+    // With a bare king White can't checkmate at all, so it's a draw.
+    let mut game = Game::from_fen("4k3/4p3/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+    game.set_time_control(TimeControl::sudden_death(1000))
+        .unwrap();
+    game.start_clock(0.0).unwrap();
+    // A move made after the flag fall is not accepted.
+    game.update_clock(2000.0);
+    assert!(game.make_move_san("Kd7", None).is_err());
+    assert_eq!(game.get_status(), GameStatus::DrawByTimeout);
+    assert_eq!(game.get_winner(), None);
+}
+
+#[test]
+fn test_resignation() {
+    let mut game = Game::new();
+    game.make_move_san("e4", None).unwrap();
+    // Either player can resign, not only the one to move.
+    game.resign(Player::White).unwrap();
+    assert_eq!(game.get_status(), GameStatus::Resignation);
+    assert_eq!(game.get_winner(), Some(Player::Black));
+    assert!(game.resign(Player::Black).is_err());
+    assert!(game.make_move_san("e5", None).is_err());
+    assert!(game
+        .pgn()
+        .contains("[Result \"0-1\"]\n[Termination \"normal\"]\n\n1. e4 0-1\n"));
+}
+
+#[test]
+fn test_resign_after_going_back() {
+    let mut game = Game::from_pgn("1. f3 e5 2. g4 Qh4# 0-1").unwrap();
+    let pgn = game.pgn();
+    game.goto_ply(2).unwrap();
+    assert!(game.resign(Player::White).is_err());
+    assert!(game.offer_draw(Player::White).is_err());
+    assert_eq!(game.pgn(), pgn);
+    assert_eq!(game.history().len(), 4);
+    game.goto_ply(4).unwrap();
+    assert_eq!(game.get_status(), GameStatus::Checkmate);
+
+    // A game in progress can't be ended in an earlier position either.
+    let mut game = Game::new();
+    game.make_move_san("e4", None).unwrap();
+    game.offer_draw(Player::White).unwrap();
+    game.undo().unwrap();
+    assert!(game.resign(Player::Black).is_err());
+    assert!(game.accept_draw(Player::Black).is_err());
+    game.redo().unwrap();
+    game.accept_draw(Player::Black).unwrap();
+    assert_eq!(game.get_status(), GameStatus::DrawByAgreement);
+    assert_eq!(game.get_san_moves(), ["e4"]);
+}
+
+#[test]
+fn test_draw_offer() {
+    let mut game = Game::new();
+    assert!(game.set_tag("Termination", "normal").is_err());
+    // An offer made before a move stands after it.
+    game.offer_draw(Player::White).unwrap();
+    game.make_move_san("e4", None).unwrap();
+    assert_eq!(game.get_draw_offer(), Some(Player::White));
+    assert!(game.accept_draw(Player::White).is_err());
+    game.decline_draw(Player::Black).unwrap();
+    assert_eq!(game.get_draw_offer(), None);
+    assert!(game.accept_draw(Player::Black).is_err());
+
+    // The offer expires when the opponent moves.
+    game.offer_draw(Player::White).unwrap();
+    game.make_move_san("e5", None).unwrap();
+    assert_eq!(game.get_draw_offer(), None);
+    assert!(game.accept_draw(Player::Black).is_err());
+
+    game.offer_draw(Player::Black).unwrap();
+    game.accept_draw(Player::White).unwrap();
+    assert_eq!(game.get_status(), GameStatus::DrawByAgreement);
+    assert_eq!(game.get_draw_offer(), None);
+    assert!(game.offer_draw(Player::White).is_err());
+
+    let reread = Game::from_pgn(&game.pgn()).unwrap();
+    assert_eq!(reread.get_status(), GameStatus::DrawByAgreement);
+    assert!(reread.pgn().contains("[Termination \"normal\"]"));
+}
+
+#[test]
+fn test_time_forfeit_import() {
+    let pgn = "[Result \"0-1\"]\n[Termination \"time forfeit\"]\n\n1. e4 e5 0-1\n";
+    let game = Game::from_pgn(pgn).unwrap();
+    assert_eq!(game.get_status(), GameStatus::Timeout);
+    assert_eq!(game.get_winner(), Some(Player::Black));
+    assert!(game.pgn().contains("[Termination \"time forfeit\"]"));
+}
+
+#[test]
+fn test_perft() {
+    let mut game = Game::new();
+    assert_eq!(game.perft(2), 400);
+    game.make_move_san("e4", None).unwrap();
+    let divided = game.perft_divide(2);
+    assert_eq!(divided.len(), 20);
+    assert_eq!(divided.iter().map(|entry| entry.nodes).sum::<u64>(), 600);
+    assert!(divided.iter().any(|entry| entry.uci == "g8f6"));
+}
+
+#[test]
+fn test_position_hash() {
+    let mut game = Game::new();
+    assert_eq!(game.get_hash(), 0x463b96181691fc9c);
+    game.make_move_san("e4", None).unwrap();
+    assert_eq!(game.get_hash(), 0x823c9b50fd114196);
+    // The same position reached by a different move order has the same hash.
+    let mut other = Game::new();
+    other.make_move_san("Nf3", None).unwrap();
+    other.make_move_san("Nf6", None).unwrap();
+    other.make_move_san("Ng1", None).unwrap();
+    other.make_move_san("Ng8", None).unwrap();
+    game.undo().unwrap();
+    assert_eq!(other.get_hash(), game.get_hash());
+}
+
+#[test]
+fn test_best_move() {
+    let mut game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    let engine_move = game.best_move(SearchLimit::depth(3)).unwrap();
+    assert_eq!(engine_move.san, "Ra8#");
+    assert_eq!(engine_move.uci, "a1a8");
+    assert_eq!(engine_move.mate, Some(1));
+    game.make_move_uci(&engine_move.uci, None).unwrap();
+    assert!(game.best_move(SearchLimit::depth(3)).is_err());
+
+    let mut game = Game::new();
+    let engine_move = game.best_move(SearchLimit::time(50)).unwrap();
+    assert!(engine_move.depth >= 1);
+    assert_eq!(engine_move.mate, None);
+    assert!(game.get_legal_moves_san().contains(&engine_move.san));
+}
+
+#[test]
+fn test_evaluate() {
+    let mut game = Game::new();
+    assert_eq!(game.evaluate(), 0);
+    for san in ["e4", "d5", "exd5"] {
+        game.make_move_san(san, None).unwrap();
+    }
+    assert!(game.evaluate() > 50);
+    let game = Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR w KQkq - 0 1").unwrap();
+    assert!(game.evaluate() < -900);
+}
+
+#[test]
+fn test_computer_move() {
+    let mut game = Game::new();
+    game.make_move_san("e4", None).unwrap();
+    let first = game.computer_move(Difficulty::Beginner, 42).unwrap();
+    let second = game.computer_move(Difficulty::Beginner, 42).unwrap();
+    assert_eq!(first.uci, second.uci);
+    assert!(game.get_legal_moves_san().contains(&first.san));
+
+    let mut game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    let engine_move = game.computer_move(Difficulty::Maximum, 0).unwrap();
+    assert_eq!(engine_move.san, "Ra8#");
+    game.make_move_uci(&engine_move.uci, None).unwrap();
+    assert!(game.computer_move(Difficulty::Maximum, 0).is_err());
+}
+
+#[test]
+fn test_hash_size() {
+    let mut game = Game::from_fen("2r3k1/5ppp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1").unwrap();
+    assert!(game.set_hash_size(0).is_err());
+    assert!(game.set_hash_size(100_000).is_err());
+    game.set_hash_size(1).unwrap();
+    let first = game.best_move(SearchLimit::depth(4)).unwrap();
+    assert_eq!((first.uci.as_str(), first.mate), ("d2d8", Some(2)));
+    // The positions stored by the first search give the same answer.
+    let second = game.best_move(SearchLimit::depth(4)).unwrap();
+    assert_eq!((second.uci, second.mate), (first.uci, first.mate));
+    game.clear_hash();
+    game.set_hash_size(2).unwrap();
+    assert_eq!(game.best_move(SearchLimit::depth(4)).unwrap().mate, Some(2));
+}